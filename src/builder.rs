@@ -0,0 +1,132 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::{
+    error::ContentError,
+    pattern::{compile, matches},
+    util::is_valid_relative_path,
+    Content, File,
+};
+
+type FileMap = BTreeMap<String, File>;
+
+/// Hashes a site directory into the `files` and `files_optional` maps of a content.json.
+pub struct ContentBuilder {
+    directory: PathBuf,
+    ignore: String,
+    optional: String,
+    includes: Vec<String>,
+}
+
+impl ContentBuilder {
+    /// `directory` is the directory holding the content.json, paths are relative to it.
+    pub fn new<P: AsRef<Path>>(directory: P) -> ContentBuilder {
+        ContentBuilder {
+            directory: directory.as_ref().to_path_buf(),
+            ignore: String::new(),
+            optional: String::new(),
+            includes: vec![],
+        }
+    }
+
+    /// Builder for `content`, taking its `ignore`, `optional` and `includes` settings.
+    pub fn from_content<P: AsRef<Path>>(site_directory: P, content: &Content) -> ContentBuilder {
+        let mut directory = site_directory.as_ref().to_path_buf();
+        if let Some((parent, _)) = content.inner_path.rsplit_once('/') {
+            directory.push(parent);
+        }
+        ContentBuilder {
+            directory,
+            ignore: content.ignore.clone(),
+            optional: content.optional.clone(),
+            includes: content.includes.keys().cloned().collect(),
+        }
+    }
+
+    /// Files matching `pattern` are left out.
+    pub fn ignore(mut self, pattern: &str) -> ContentBuilder {
+        self.ignore = pattern.to_owned();
+        self
+    }
+
+    /// Files matching `pattern` go into `files_optional`.
+    pub fn optional(mut self, pattern: &str) -> ContentBuilder {
+        self.optional = pattern.to_owned();
+        self
+    }
+
+    /// The directory of the included content.json is left out, it is hashed by that content.json.
+    pub fn include(mut self, inner_path: &str) -> ContentBuilder {
+        self.includes.push(inner_path.to_owned());
+        self
+    }
+
+    /// Returns the `files` and `files_optional` maps.
+    pub fn build(&self) -> Result<(FileMap, FileMap), ContentError> {
+        let ignore = compile(&self.ignore)?;
+        let optional = compile(&self.optional)?;
+        let included_directories: Vec<String> = self
+            .includes
+            .iter()
+            .filter_map(|inner_path| inner_path.rsplit_once('/'))
+            .map(|(directory, _)| format!("{}/", directory))
+            .collect();
+
+        let mut files = BTreeMap::new();
+        let mut files_optional = BTreeMap::new();
+        for relative_path in walk(&self.directory)? {
+            let file_name = relative_path.rsplit('/').next().unwrap_or(&relative_path);
+            let ignored = file_name == "content.json"
+                || file_name.starts_with('.')
+                || file_name.ends_with("-old")
+                || file_name.ends_with("-new")
+                || !is_valid_relative_path(&relative_path)
+                || included_directories
+                    .iter()
+                    .any(|directory| relative_path.starts_with(directory))
+                || matches(&ignore, &relative_path);
+            if ignored {
+                continue;
+            }
+            let file = File::from_reader(fs::File::open(self.directory.join(&relative_path))?)?;
+            if matches(&optional, &relative_path) {
+                files_optional.insert(relative_path, file);
+            } else {
+                files.insert(relative_path, file);
+            }
+        }
+        Ok((files, files_optional))
+    }
+
+    /// Replaces `files` and `files_optional` of `content` with the hashed directory.
+    pub fn build_into(&self, content: &mut Content) -> Result<(), ContentError> {
+        let (files, files_optional) = self.build()?;
+        content.files = files;
+        content.files_optional = files_optional;
+        Ok(())
+    }
+}
+
+/// Relative paths of all files below `directory`, separated by `/`.
+pub(crate) fn walk(directory: &Path) -> Result<Vec<String>, ContentError> {
+    let mut paths = vec![];
+    let mut pending = vec![(directory.to_path_buf(), String::new())];
+    while let Some((path, relative_path)) = pending.pop() {
+        for entry in fs::read_dir(&path)? {
+            let entry = entry?;
+            let name = entry.file_name().to_string_lossy().into_owned();
+            let entry_relative_path = if relative_path.is_empty() {
+                name
+            } else {
+                format!("{}/{}", relative_path, name)
+            };
+            if entry.file_type()?.is_dir() {
+                pending.push((entry.path(), entry_relative_path));
+            } else {
+                paths.push(entry_relative_path);
+            }
+        }
+    }
+    Ok(paths)
+}
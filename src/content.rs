@@ -1,12 +1,32 @@
-use std::{collections::BTreeMap, default::Default, time::SystemTime};
+use std::{
+    collections::BTreeMap,
+    default::Default,
+    io::{Read, Write},
+    time::{Duration, SystemTime},
+};
 
 use json_filter_sorted::sort::sort_json;
 use serde::{Deserialize, Serialize};
-use serde_json::{json, Error, Value};
+use serde_bytes::ByteBuf;
+use serde_json::Value;
 
 use crate::{
+    diff::{changed_keys, ContentDiff, MapDiff},
+    error::ContentError,
+    file::FileError,
+    legacy::{verify_legacy_signature, LegacySign},
+    patch::{self, DiffAction, PatchError},
+    pattern::Pattern,
+    signer::{PrivateKeySigner, Signer},
+    surrogate::LoneSurrogates,
+    user_contents::user_address,
     util::{is_default, Number},
-    zeruformatter, File, Include, UserContents,
+    verification::{
+        verify_signature, CertError, ThresholdError, ThresholdReport, UpdateError, VerifyError,
+        VerifyFailure, VerifyReport,
+    },
+    zeruformatter::{self, ZeruFormatter},
+    File, Include, UserContents,
 };
 
 #[derive(Serialize, Deserialize, Default, Clone)]
@@ -51,6 +71,12 @@ pub struct Content {
 
     #[serde(skip_serializing_if = "is_default")]
     pub user_contents: Option<UserContents>,
+    #[serde(skip_serializing_if = "is_default")]
+    pub cert_auth_type: String,
+    #[serde(skip_serializing_if = "is_default")]
+    pub cert_user_id: String,
+    #[serde(skip_serializing_if = "is_default")]
+    pub cert_sign: String,
 
     pub ignore: String,
     #[serde(skip_serializing_if = "is_default")]
@@ -60,7 +86,9 @@ pub struct Content {
     pub postmessage_nonce_security: bool,
 
     #[serde(skip_serializing_if = "is_default")]
-    sign: Vec<f64>, // DEPRECATED
+    sign: LegacySign, // DEPRECATED
+    #[serde(skip_serializing_if = "is_default")]
+    pub signers: Vec<String>,
     #[serde(skip_serializing_if = "is_default")]
     pub signers_sign: String,
     #[serde(skip_serializing_if = "is_default")]
@@ -83,17 +111,56 @@ pub struct Content {
     pub zeronet_version: String,
 
     #[serde(skip_serializing, skip_deserializing)]
-    _raw: (bool, Value),
+    _raw: Value,
+    #[serde(skip_serializing, skip_deserializing)]
+    _lone_surrogates: Option<LoneSurrogates>,
+}
+
+/// How far ahead of the clock `modified` may be, one day as in ZeroNet.
+pub const MAX_FUTURE: Duration = Duration::from_secs(60 * 60 * 24);
+
+/// Canonical bytes of `value`. A content.json with lone surrogates only dumps exactly
+/// through `Content::write_canonical`.
+pub fn dump<T: Serialize>(value: T) -> Result<String, ContentError> {
+    Content::dump_value(serde_json::to_value(value)?, None)
+}
+
+/// Addresses allowed to sign the content.json at `inner_path`, following ZeroNet's
+/// getValidSigners: `parent` is the root itself for the root content.json, otherwise
+/// the content.json whose `includes` or `user_contents` govern `inner_path`. Without
+/// the user content.json, `user_contents` rules are matched as `n-a/n-a`.
+pub fn valid_signers(inner_path: &str, parent: &Content) -> Result<Vec<String>, ContentError> {
+    signers_for(inner_path, parent, None)
+}
+
+fn signers_for(
+    inner_path: &str,
+    parent: &Content,
+    content: Option<&Content>,
+) -> Result<Vec<String>, ContentError> {
+    if inner_path == "content.json" {
+        return Ok(parent.valid_signers());
+    }
+    let mut valid_signers = vec![];
+    let parent_directory = parent.inner_path.strip_suffix("content.json").unwrap_or("");
+    if let Some(relative_path) = inner_path.strip_prefix(parent_directory) {
+        if let Some(include) = parent.includes.get(relative_path) {
+            valid_signers.extend_from_slice(include.signers());
+        } else if let Some(user_contents) = &parent.user_contents {
+            // The directory of a user content.json is named after the user's address.
+            if let Some(user_address) = user_address(relative_path) {
+                valid_signers = user_contents.valid_signers(user_address, content)?;
+            }
+        }
+    }
+    if !valid_signers.contains(&parent.address) {
+        valid_signers.push(parent.address.clone());
+    }
+    Ok(valid_signers)
 }
 
-pub fn dump<T: Serialize>(value: T) -> Result<String, serde_json::error::Error> {
-    zeruformatter::to_string_zero(
-        &sort_json(json!(value))
-            .unwrap()
-            .as_object()
-            .map(|x| x.to_owned())
-            .unwrap(),
-    )
+pub(crate) fn now() -> Number {
+    Number::from(SystemTime::now())
 }
 
 impl Content {
@@ -102,101 +169,528 @@ impl Content {
             title: address.to_owned(),
             address,
             address_index,
-            modified: Number::Integer(
-                SystemTime::now()
-                    .duration_since(SystemTime::UNIX_EPOCH)
-                    .unwrap()
-                    .as_secs() as usize,
-            ),
+            modified: now(),
             inner_path: "content.json".to_owned(),
             postmessage_nonce_security: true,
             ..Default::default()
         }
     }
 
-    pub fn from_buf(buf: serde_bytes::ByteBuf) -> Result<Content, Error> {
-        let is_properly_escaped;
-        let _raw: serde_json::Value = {
-            let mut string = String::from_utf8(buf.to_vec()).unwrap();
-            is_properly_escaped = string.contains("\\\\u");
-            if !is_properly_escaped {
-                string = string.replace("\\u", "\\\\u");
+    pub fn from_buf(buf: ByteBuf) -> Result<Content, ContentError> {
+        let string = std::str::from_utf8(&buf)?;
+        let (string, lone_surrogates) = LoneSurrogates::encode(string)?;
+        let value: Value = serde_json::from_str(&string)?;
+        if !value.is_object() {
+            return Err(ContentError::Schema(
+                "content is not a JSON object".to_owned(),
+            ));
+        }
+        let mut content = Content::deserialize(&value)?;
+        // Signing dumps the parsed value as Python would, which escapes strings the same
+        // way whether the file had `\u` escapes or raw UTF-8.
+        content._raw = value;
+        content._lone_surrogates = lone_surrogates;
+        Ok(content)
+    }
+
+    pub fn raw(&self) -> Result<Value, ContentError> {
+        Ok(serde_json::to_value(self)?)
+    }
+
+    /// Size ZeroNet counts for the content.json itself, `len(json.dumps(content, indent=1))`
+    /// of the parsed file.
+    pub(crate) fn dumped_size(&self) -> Result<usize, ContentError> {
+        let mut buf = vec![];
+        let formatter = ZeruFormatter::python_indent(1).with_lone_surrogates(self._lone_surrogates);
+        if self._raw.is_object() {
+            zeruformatter::to_writer_with(&mut buf, &self._raw, formatter)?;
+        } else {
+            zeruformatter::to_writer_with(&mut buf, self, formatter)?;
+        }
+        Ok(buf.len())
+    }
+
+    /// A copy without `files`, `files_optional` and the raw value, which are the bulk of a
+    /// large content.json, and without signatures unless `with_signs` is set.
+    fn shallow(&self, with_signs: bool) -> Content {
+        let Content {
+            address,
+            address_index,
+            domain,
+            title,
+            description,
+            favicon,
+            cloneable,
+            cloned_from,
+            clone_root,
+            background_color,
+            background_color_dark,
+            viewport,
+            translate,
+            user_contents,
+            cert_auth_type,
+            cert_user_id,
+            cert_sign,
+            ignore,
+            inner_path,
+            modified,
+            postmessage_nonce_security,
+            signers,
+            signers_sign,
+            signs_required,
+            includes,
+            merged_type,
+            optional,
+            settings,
+            other,
+            zeronet_version,
+            files: _,
+            files_optional: _,
+            sign,
+            signs,
+            _raw: _,
+            _lone_surrogates,
+        } = self;
+        let (sign, signs) = if with_signs {
+            (sign.clone(), signs.clone())
+        } else {
+            (LegacySign::default(), BTreeMap::new())
+        };
+        Content {
+            address: address.clone(),
+            domain: domain.clone(),
+            title: title.clone(),
+            description: description.clone(),
+            favicon: favicon.clone(),
+            cloned_from: cloned_from.clone(),
+            clone_root: clone_root.clone(),
+            background_color: background_color.clone(),
+            background_color_dark: background_color_dark.clone(),
+            viewport: viewport.clone(),
+            translate: translate.clone(),
+            user_contents: user_contents.clone(),
+            cert_auth_type: cert_auth_type.clone(),
+            cert_user_id: cert_user_id.clone(),
+            cert_sign: cert_sign.clone(),
+            ignore: ignore.clone(),
+            inner_path: inner_path.clone(),
+            signers: signers.clone(),
+            signers_sign: signers_sign.clone(),
+            includes: includes.clone(),
+            merged_type: merged_type.clone(),
+            optional: optional.clone(),
+            settings: settings.clone(),
+            other: other.clone(),
+            zeronet_version: zeronet_version.clone(),
+            address_index: *address_index,
+            cloneable: *cloneable,
+            postmessage_nonce_security: *postmessage_nonce_security,
+            signs_required: *signs_required,
+            modified: *modified,
+            files: BTreeMap::new(),
+            files_optional: BTreeMap::new(),
+            sign,
+            signs,
+            _raw: Value::Null,
+            _lone_surrogates: *_lone_surrogates,
+        }
+    }
+
+    /// Writes the same bytes as `dump(self)`, streaming `files` and `files_optional`
+    /// instead of building a `Value` of the whole content first.
+    pub fn write_canonical<W: Write>(&self, writer: W) -> Result<(), ContentError> {
+        self.write_canonical_with(writer, true)
+    }
+
+    fn write_canonical_with<W: Write>(
+        &self,
+        mut writer: W,
+        with_signs: bool,
+    ) -> Result<(), ContentError> {
+        let value = serde_json::to_value(self.shallow(with_signs))?;
+        let mut map = match sort_json(value).map_err(|err| ContentError::Schema(err.to_string()))? {
+            Value::Object(map) => map,
+            _ => {
+                return Err(ContentError::Schema(
+                    "content is not a JSON object".to_owned(),
+                ))
             }
-            serde_json::from_str(&string).unwrap()
         };
-        let content = match serde_json::from_slice(&buf) {
-            Ok(c) => c,
-            Err(err) => return Err(err),
+        map.remove("files");
+        map.remove("files_optional");
+        let mut keys: Vec<&str> = map.keys().map(String::as_str).collect();
+        keys.push("files");
+        if !self.files_optional.is_empty() {
+            keys.push("files_optional");
+        }
+        keys.sort_unstable();
+
+        let python = || ZeruFormatter::python().with_lone_surrogates(self._lone_surrogates);
+        writer.write_all(b"{")?;
+        for (index, key) in keys.into_iter().enumerate() {
+            if index > 0 {
+                writer.write_all(b", ")?;
+            }
+            zeruformatter::to_writer_with(&mut writer, key, python())?;
+            writer.write_all(b": ")?;
+            match key {
+                "files" => zeruformatter::to_writer_with(&mut writer, &self.files, python())?,
+                "files_optional" => {
+                    zeruformatter::to_writer_with(&mut writer, &self.files_optional, python())?
+                }
+                key => zeruformatter::to_writer_with(&mut writer, &map[key], python())?,
+            }
+        }
+        writer.write_all(b"}")?;
+        Ok(())
+    }
+
+    /// The canonical bytes that are signed, which leave out `signs` and `sign`.
+    fn dump(&self) -> Result<String, ContentError> {
+        let mut buf = vec![];
+        self.write_canonical_with(&mut buf, false)?;
+        String::from_utf8(buf).map_err(|err| ContentError::Utf8(err.utf8_error()))
+    }
+
+    fn dump_value(
+        value: Value,
+        lone_surrogates: Option<LoneSurrogates>,
+    ) -> Result<String, ContentError> {
+        let sorted = sort_json(value).map_err(|err| ContentError::Schema(err.to_string()))?;
+        let map = match sorted.as_object() {
+            Some(map) => map,
+            None => {
+                return Err(ContentError::Schema(
+                    "content is not a JSON object".to_owned(),
+                ))
+            }
         };
-        let content = Content {
-            _raw: (is_properly_escaped, _raw),
-            ..content
+        let formatter = ZeruFormatter::python().with_lone_surrogates(lone_surrogates);
+        Ok(zeruformatter::to_string_with(map, formatter)?)
+    }
+
+    fn signed_data(&self) -> Result<String, ContentError> {
+        self.raw_dump_without(&["signs", "sign"])
+    }
+
+    /// Canonical bytes of the parsed content.json without `keys`.
+    fn raw_dump_without(&self, keys: &[&str]) -> Result<String, ContentError> {
+        let mut raw = self._raw.clone();
+        let map = match raw.as_object_mut() {
+            Some(map) => map,
+            None => {
+                return Err(ContentError::Schema(
+                    "raw content is not a JSON object".to_owned(),
+                ))
+            }
         };
-        Ok(content)
+        for key in keys {
+            map.remove(*key);
+        }
+        Self::dump_value(raw, self._lone_surrogates)
     }
 
-    pub fn raw(&self) -> serde_json::Value {
-        let mut data = serde_json::to_string(&self).unwrap();
-        let is_properly_escaped = self._raw.0;
-        if !is_properly_escaped {
-            data = data.replace("\\\\u", "\\u");
+    fn unverified(&self, signer: &str, reason: VerifyFailure) -> VerifyError {
+        VerifyError {
+            signer: signer.to_owned(),
+            reason,
+            hash: None,
         }
-        serde_json::from_str(&data).unwrap()
     }
 
-    pub fn is_properly_escaped(&self) -> bool {
-        self._raw.0
+    /// The deprecated `sign` of very old sites, if present.
+    pub fn legacy_sign(&self) -> Option<&LegacySign> {
+        if self.sign.integers().is_empty() {
+            None
+        } else {
+            Some(&self.sign)
+        }
     }
 
-    fn cleared(&self) -> Content {
-        let mut new_content = self.clone();
-        new_content.signs = BTreeMap::new();
-        new_content.sign = vec![];
-        new_content
+    /// Verifies the deprecated `sign` against the site address.
+    pub fn verify_legacy_sign(&self) -> Result<VerifyReport, ContentError> {
+        let sign = match self.legacy_sign() {
+            Some(sign) => sign,
+            None => {
+                return Err(self
+                    .unverified(&self.address, VerifyFailure::MissingSignature)
+                    .into())
+            }
+        };
+        // The old scheme signed everything but `sign`, `signs` included.
+        let data = self.raw_dump_without(&["sign"])?;
+        Ok(verify_legacy_signature(&data, &self.address, sign)?)
     }
 
-    fn dump(&self) -> Result<String, serde_json::error::Error> {
-        zeruformatter::to_string_zero(
-            &sort_json(json!(self.cleared()))
-                .unwrap()
-                .as_object()
-                .map(|x| x.to_owned())
-                .unwrap(),
-        )
+    pub fn verify(&self, key: String) -> Result<VerifyReport, ContentError> {
+        let signature = match self.signs.get(&key) {
+            Some(v) => v,
+            None => {
+                return Err(self
+                    .unverified(&key, VerifyFailure::MissingSignature)
+                    .into())
+            }
+        };
+        let data = self.signed_data()?;
+        Ok(verify_signature(&data, &key, signature)?)
+    }
+
+    /// Checks every entry in `signs` and succeeds when at least `signs_required`
+    /// of them are valid signatures from `valid_signers`.
+    pub fn verify_threshold(
+        &self,
+        valid_signers: &[String],
+    ) -> Result<ThresholdReport, ThresholdError> {
+        let mut report = ThresholdReport {
+            required: self.effective_signs_required(),
+            ..Default::default()
+        };
+        let data = self.signed_data().map_err(ThresholdError::Content)?;
+        for (signer, signature) in &self.signs {
+            let result = if !valid_signers.contains(signer) {
+                Err(self.unverified(signer, VerifyFailure::UnknownSigner))
+            } else {
+                verify_signature(&data, signer, signature)
+            };
+            match result {
+                Ok(passed) => report.passed.push(passed),
+                Err(failed) => report.failed.push(failed),
+            }
+        }
+        if report.is_satisfied() {
+            Ok(report)
+        } else {
+            Err(ThresholdError::NotEnoughSignatures(report))
+        }
+    }
+
+    fn effective_signs_required(&self) -> usize {
+        self.signs_required.max(1)
+    }
+
+    /// Signers allowed to sign the root content.json: `signers` plus the site address.
+    pub fn valid_signers(&self) -> Vec<String> {
+        let mut valid_signers = self.signers.clone();
+        if !valid_signers.contains(&self.address) {
+            valid_signers.push(self.address.clone());
+        }
+        valid_signers
     }
 
-    fn dump_value(value: Value) -> Result<String, serde_json::error::Error> {
-        zeruformatter::to_string_zero(
-            &sort_json(value)
-                .unwrap()
-                .as_object()
-                .map(|x| x.to_owned())
-                .unwrap(),
+    /// The string the site address signs into `signers_sign`.
+    pub fn signers_data(&self) -> String {
+        format!(
+            "{}:{}",
+            self.effective_signs_required(),
+            self.valid_signers().join(",")
         )
     }
 
-    // TODO: verify should probably return more than just a bool
-    pub fn verify(&self, key: String) -> bool {
-        let mut raw = self._raw.clone();
-        let map = raw.1.as_object_mut().unwrap();
-        map.remove("signs");
-        map.remove("sign");
-        let signature = match self.signs.get(&key) {
-            Some(v) => v,
-            None => return false,
+    /// Signs the content with `privkey` and embeds the signature in `signs`,
+    /// returning the bytes to publish.
+    pub fn sign_in_place(&mut self, privkey: &str) -> Result<ByteBuf, ContentError> {
+        self.sign_in_place_with(&PrivateKeySigner::new(privkey)?)
+    }
+
+    /// Like `sign_in_place`, but signs through `signer` instead of a private key.
+    pub fn sign_in_place_with<S: Signer + ?Sized>(
+        &mut self,
+        signer: &S,
+    ) -> Result<ByteBuf, ContentError> {
+        let address = signer.address();
+        self.modified = now();
+        self.signs = BTreeMap::new();
+        self.sign = LegacySign::default();
+        if self.inner_path == "content.json" && address == self.address {
+            self.signs_required = self.effective_signs_required();
+            self.signers_sign = signer.sign(self.signers_data().as_bytes())?;
+        }
+        let signature = signer.sign(self.dump()?.as_bytes())?;
+        self.signs.insert(address, signature);
+        let mut buf = vec![];
+        self.write_canonical(&mut buf)?;
+        let buf = ByteBuf::from(buf);
+        let parsed = Content::from_buf(buf.clone())?;
+        self._raw = parsed._raw;
+        self._lone_surrogates = parsed._lone_surrogates;
+        Ok(buf)
+    }
+
+    pub fn sign_signers(&self, privkey: String) -> Result<String, ContentError> {
+        PrivateKeySigner::new(&privkey)?.sign(self.signers_data().as_bytes())
+    }
+
+    pub fn verify_signers_sign(&self) -> Result<VerifyReport, VerifyError> {
+        if self.signers_sign.is_empty() {
+            return Err(VerifyError {
+                signer: self.address.clone(),
+                reason: VerifyFailure::MissingSignature,
+                hash: None,
+            });
+        }
+        verify_signature(&self.signers_data(), &self.address, &self.signers_sign)
+    }
+
+    /// Verifies a root content.json: `signers_sign` when there are extra signers,
+    /// then the signatures in `signs` against the valid signers.
+    pub fn verify_root(&self) -> Result<ThresholdReport, ThresholdError> {
+        let valid_signers = self.valid_signers();
+        if valid_signers.len() > 1 {
+            self.verify_signers_sign()
+                .map_err(ThresholdError::InvalidSignersSign)?;
+        }
+        self.verify_threshold(&valid_signers)
+    }
+
+    /// Verifies a nested content.json against the signers `parent` allows for it, with
+    /// the `user_contents` rules matched against this content.
+    pub fn verify_with_parent(&self, parent: &Content) -> Result<ThresholdReport, ThresholdError> {
+        if self.inner_path == "content.json" {
+            return self.verify_root();
+        }
+        let valid_signers =
+            signers_for(&self.inner_path, parent, Some(self)).map_err(ThresholdError::Content)?;
+        self.verify_threshold(&valid_signers)
+    }
+
+    pub fn sign(&self, privkey: String) -> Result<String, ContentError> {
+        PrivateKeySigner::new(&privkey)?.sign(self.dump()?.as_bytes())
+    }
+
+    /// The string a cert signer signs for the user at `user_address`.
+    pub fn cert_data(&self, user_address: &str) -> String {
+        let name = match self.cert_user_id.rsplit_once('@') {
+            Some((name, _)) => name,
+            None => &self.cert_user_id,
         };
-        let mut data = Self::dump_value(raw.1).unwrap();
-        let is_properly_escaped = self._raw.0;
-        if !is_properly_escaped {
-            data = data.replace("\\\\u", "\\u");
+        format!("{}#{}/{}", user_address, self.cert_auth_type, name)
+    }
+
+    /// Checks `cert_sign` against the cert signers `user_contents` accepts, like ZeroNet's
+    /// verifyCert. Passes if `user_contents` has neither `cert_signers` nor a pattern.
+    pub fn verify_cert(
+        &self,
+        user_address: &str,
+        user_contents: &UserContents,
+    ) -> Result<(), CertError> {
+        if user_contents.cert_signers.is_empty() && user_contents.cert_signers_pattern.is_empty() {
+            return Ok(());
         }
-        let bytes = data.as_bytes();
-        let result = zeronet_cryptography::verify(bytes, &key, &signature);
-        result.is_ok()
+        if self.cert_user_id.is_empty() {
+            return Err(CertError::MissingCert);
+        }
+        let domain = match self.cert_user_id.split_once('@') {
+            Some((_, domain)) if !domain.contains('@') => domain,
+            _ => return Err(CertError::InvalidUserId(self.cert_user_id.clone())),
+        };
+        let cert_signers = match user_contents.cert_signers.get(domain) {
+            Some(cert_signers) if !cert_signers.is_empty() => cert_signers.clone(),
+            // A domain matching the pattern is the address of its cert signer.
+            _ if !user_contents.cert_signers_pattern.is_empty()
+                && Pattern::safe(&user_contents.cert_signers_pattern)?.is_match(domain) =>
+            {
+                vec![domain.to_owned()]
+            }
+            _ => return Err(CertError::UnknownCertSigner(domain.to_owned())),
+        };
+        let data = self.cert_data(user_address);
+        let mut result = Ok(());
+        for cert_signer in &cert_signers {
+            match verify_signature(&data, cert_signer, &self.cert_sign) {
+                Ok(_) => return Ok(()),
+                Err(err) => result = Err(CertError::Signature(err)),
+            }
+        }
+        result
     }
 
-    pub fn sign(&self, privkey: String) -> String {
-        zeronet_cryptography::sign(self.dump().unwrap().as_bytes(), &privkey).unwrap()
+    pub fn is_newer_than(&self, other: &Content) -> bool {
+        self.modified > other.modified
+    }
+
+    /// Checks the content may replace `accepted`, the version accepted before if any:
+    /// it must be strictly newer and at most `MAX_FUTURE` ahead of `now`.
+    pub fn check_update(
+        &self,
+        accepted: Option<&Content>,
+        now: SystemTime,
+    ) -> Result<(), UpdateError> {
+        let modified = self.modified.as_f64();
+        let now = now
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs_f64();
+        if modified > now + MAX_FUTURE.as_secs_f64() {
+            return Err(UpdateError::FarFuture { modified, now });
+        }
+        match accepted {
+            Some(accepted) if !self.is_newer_than(accepted) => Err(UpdateError::NotNewer {
+                modified,
+                accepted: accepted.modified.as_f64(),
+            }),
+            _ => Ok(()),
+        }
+    }
+
+    /// What changed from `old` to this version, leaving out `signs` and `sign`.
+    pub fn diff(&self, old: &Content) -> ContentDiff {
+        let mut fields = changed_keys(&old.other, &self.other);
+        let mut changed = |name: &str, changed: bool| {
+            if changed {
+                fields.push(name.to_owned());
+            }
+        };
+        changed("address", self.address != old.address);
+        changed("address_index", self.address_index != old.address_index);
+        changed("domain", self.domain != old.domain);
+        changed("title", self.title != old.title);
+        changed("description", self.description != old.description);
+        changed("favicon", self.favicon != old.favicon);
+        changed("cloneable", self.cloneable != old.cloneable);
+        changed("cloned_from", self.cloned_from != old.cloned_from);
+        changed("clone_root", self.clone_root != old.clone_root);
+        changed(
+            "background-color",
+            self.background_color != old.background_color,
+        );
+        changed(
+            "background-color-dark",
+            self.background_color_dark != old.background_color_dark,
+        );
+        changed("viewport", self.viewport != old.viewport);
+        changed("translate", self.translate != old.translate);
+        changed("cert_auth_type", self.cert_auth_type != old.cert_auth_type);
+        changed("cert_user_id", self.cert_user_id != old.cert_user_id);
+        changed("cert_sign", self.cert_sign != old.cert_sign);
+        changed("ignore", self.ignore != old.ignore);
+        changed("inner_path", self.inner_path != old.inner_path);
+        changed("modified", self.modified != old.modified);
+        changed(
+            "postmessage_nonce_security",
+            self.postmessage_nonce_security != old.postmessage_nonce_security,
+        );
+        changed("signers", self.signers != old.signers);
+        changed("signers_sign", self.signers_sign != old.signers_sign);
+        changed("signs_required", self.signs_required != old.signs_required);
+        changed("merged_type", self.merged_type != old.merged_type);
+        changed("optional", self.optional != old.optional);
+        changed("settings", self.settings != old.settings);
+        changed(
+            "zeronet_version",
+            self.zeronet_version != old.zeronet_version,
+        );
+        fields.sort();
+
+        let no_user_contents = UserContents::default();
+        let user_contents = self.user_contents.as_ref().unwrap_or(&no_user_contents);
+        let old_user_contents = old.user_contents.as_ref().unwrap_or(&no_user_contents);
+        ContentDiff {
+            files: MapDiff::between(&old.files, &self.files),
+            files_optional: MapDiff::between(&old.files_optional, &self.files_optional),
+            includes: MapDiff::between(&old.includes, &self.includes),
+            user_contents: user_contents.diff(old_user_contents),
+            fields,
+        }
     }
 
     pub fn get_file(&self, inner_path: &str) -> Option<File> {
@@ -207,4 +701,25 @@ impl Content {
         }
         None
     }
+
+    /// Applies an update diff to the old data of `inner_path` and checks the result
+    /// against its entry, so a small edit does not need the whole file.
+    pub fn patch_file(
+        &self,
+        inner_path: &str,
+        old: &[u8],
+        actions: &[DiffAction],
+    ) -> Result<Vec<u8>, PatchError> {
+        let new = patch::patch(old, actions)?;
+        self.verify_file(inner_path, &new[..])?;
+        Ok(new)
+    }
+
+    /// Checks the data of `inner_path`, relative to this content.json, against its entry.
+    pub fn verify_file<R: Read>(&self, inner_path: &str, reader: R) -> Result<(), FileError> {
+        match self.get_file(inner_path) {
+            Some(file) => file.verify_reader(reader),
+            None => Err(FileError::Unknown(inner_path.to_owned())),
+        }
+    }
 }
@@ -0,0 +1,92 @@
+use std::collections::BTreeMap;
+
+/// What changed between two versions of a content.json, as returned by `Content::diff`.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct ContentDiff {
+    pub files: MapDiff,
+    pub files_optional: MapDiff,
+    pub includes: MapDiff,
+    pub user_contents: UserContentsDiff,
+    /// Other top-level keys whose value changed, by their JSON name. Signatures are left out.
+    pub fields: Vec<String>,
+}
+
+impl ContentDiff {
+    pub fn is_empty(&self) -> bool {
+        self.files.is_empty()
+            && self.files_optional.is_empty()
+            && self.includes.is_empty()
+            && self.user_contents.is_empty()
+            && self.fields.is_empty()
+    }
+}
+
+/// Keys added, removed or with a different value, in key order.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct MapDiff {
+    pub added: Vec<String>,
+    pub removed: Vec<String>,
+    pub modified: Vec<String>,
+}
+
+impl MapDiff {
+    pub fn between<V: PartialEq>(old: &BTreeMap<String, V>, new: &BTreeMap<String, V>) -> MapDiff {
+        let mut diff = MapDiff::default();
+        for (key, value) in new {
+            match old.get(key) {
+                None => diff.added.push(key.clone()),
+                Some(old_value) if old_value != value => diff.modified.push(key.clone()),
+                Some(_) => {}
+            }
+        }
+        diff.removed = old
+            .keys()
+            .filter(|key| !new.contains_key(*key))
+            .cloned()
+            .collect();
+        diff
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.modified.is_empty()
+    }
+
+    /// Added and modified keys, such as the files an updater has to download.
+    pub fn changed(&self) -> impl Iterator<Item = &str> {
+        self.added.iter().chain(&self.modified).map(String::as_str)
+    }
+}
+
+/// Changes to the `user_contents` rules, where a missing section counts as an empty one.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct UserContentsDiff {
+    pub permissions: MapDiff,
+    pub permission_rules: MapDiff,
+    pub cert_signers: MapDiff,
+    /// Other keys whose value changed, such as `archived` or `cert_signers_pattern`.
+    pub fields: Vec<String>,
+}
+
+impl UserContentsDiff {
+    pub fn is_empty(&self) -> bool {
+        self.permissions.is_empty()
+            && self.permission_rules.is_empty()
+            && self.cert_signers.is_empty()
+            && self.fields.is_empty()
+    }
+}
+
+/// Names whose values differ, for keys in either map.
+pub(crate) fn changed_keys<V: PartialEq>(
+    old: &BTreeMap<String, V>,
+    new: &BTreeMap<String, V>,
+) -> Vec<String> {
+    let mut keys: Vec<String> = new
+        .iter()
+        .filter(|(key, value)| old.get(*key) != Some(*value))
+        .map(|(key, _)| key.clone())
+        .collect();
+    keys.extend(old.keys().filter(|key| !new.contains_key(*key)).cloned());
+    keys.sort();
+    keys
+}
@@ -0,0 +1,79 @@
+use std::error::Error;
+use std::fmt::{self, Display};
+use std::io;
+use std::str::Utf8Error;
+
+use serde_json::error::Category;
+
+use crate::verification::VerifyError;
+
+#[derive(Debug)]
+pub enum ContentError {
+    /// The content is not valid UTF-8.
+    Utf8(Utf8Error),
+    /// The content is not valid JSON.
+    Json(serde_json::Error),
+    /// A signature did not verify.
+    Crypto(VerifyError),
+    /// The content could not be signed with the given key.
+    Sign(String),
+    /// The JSON does not have the shape of a content.json.
+    Schema(String),
+    /// A pattern such as `ignore` or `optional` is not a valid regex.
+    Pattern(String),
+    Io(io::Error),
+}
+
+impl Display for ContentError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ContentError::Utf8(err) => write!(f, "invalid utf-8: {}", err),
+            ContentError::Json(err) => write!(f, "invalid json: {}", err),
+            ContentError::Crypto(err) => write!(f, "{}", err),
+            ContentError::Sign(err) => write!(f, "signing failed: {}", err),
+            ContentError::Schema(err) => write!(f, "invalid content: {}", err),
+            ContentError::Pattern(err) => write!(f, "invalid pattern: {}", err),
+            ContentError::Io(err) => write!(f, "io error: {}", err),
+        }
+    }
+}
+
+impl Error for ContentError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ContentError::Utf8(err) => Some(err),
+            ContentError::Json(err) => Some(err),
+            ContentError::Crypto(err) => Some(err),
+            ContentError::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<Utf8Error> for ContentError {
+    fn from(err: Utf8Error) -> Self {
+        ContentError::Utf8(err)
+    }
+}
+
+impl From<serde_json::Error> for ContentError {
+    fn from(err: serde_json::Error) -> Self {
+        match err.classify() {
+            Category::Data => ContentError::Schema(err.to_string()),
+            Category::Io => ContentError::Io(io::Error::from(err)),
+            _ => ContentError::Json(err),
+        }
+    }
+}
+
+impl From<io::Error> for ContentError {
+    fn from(err: io::Error) -> Self {
+        ContentError::Io(err)
+    }
+}
+
+impl From<VerifyError> for ContentError {
+    fn from(err: VerifyError) -> Self {
+        ContentError::Crypto(err)
+    }
+}
@@ -1,7 +1,11 @@
 use serde::{Deserialize, Serialize};
 use std::cmp::PartialEq;
 use std::default::Default;
-use std::fmt::Debug;
+use std::error::Error;
+use std::fmt::{self, Debug, Display};
+use std::io::{self, Read};
+
+use crate::util::sha512sum_reader;
 
 #[derive(Serialize, Deserialize, Default, PartialEq, Clone)]
 pub struct File {
@@ -9,6 +13,72 @@ pub struct File {
     pub size: usize,
 }
 
+impl File {
+    pub fn from_reader<R: Read>(reader: R) -> io::Result<File> {
+        let (sha512, size) = sha512sum_reader(reader)?;
+        Ok(File { sha512, size })
+    }
+
+    /// Streams `reader` and checks its size and hash against this entry. Reading stops
+    /// one byte past the expected size, so a longer stream reports `size + 1`.
+    pub fn verify_reader<R: Read>(&self, reader: R) -> Result<(), FileError> {
+        let limit = (self.size as u64).saturating_add(1);
+        let actual = File::from_reader(reader.take(limit)).map_err(FileError::Io)?;
+        if actual.size != self.size {
+            return Err(FileError::Size {
+                expected: self.size,
+                actual: actual.size,
+            });
+        }
+        if actual.sha512 != self.sha512 {
+            return Err(FileError::Hash {
+                expected: self.sha512.clone(),
+                actual: actual.sha512,
+            });
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+pub enum FileError {
+    /// The file is not listed in `files` or `files_optional`.
+    Unknown(String),
+    Size {
+        expected: usize,
+        actual: usize,
+    },
+    Hash {
+        expected: String,
+        actual: String,
+    },
+    Io(io::Error),
+}
+
+impl Display for FileError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FileError::Unknown(inner_path) => write!(f, "{} is not in content.json", inner_path),
+            FileError::Size { expected, actual } => {
+                write!(f, "size mismatch: expected {}, got {}", expected, actual)
+            }
+            FileError::Hash { expected, actual } => {
+                write!(f, "sha512 mismatch: expected {}, got {}", expected, actual)
+            }
+            FileError::Io(err) => write!(f, "io error: {}", err),
+        }
+    }
+}
+
+impl Error for FileError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            FileError::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
 impl Debug for File {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(
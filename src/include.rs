@@ -1,6 +1,8 @@
-use crate::util::is_default;
+use crate::{error::ContentError, pattern::Pattern, util::is_default};
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
 use std::cmp::PartialEq;
+use std::collections::BTreeMap;
 use std::default::Default;
 
 #[derive(Serialize, Deserialize, Default, PartialEq, Clone)]
@@ -15,4 +17,90 @@ pub struct Include {
 	includes_allowed: bool,
 	#[serde(skip_serializing_if = "is_default")]
 	max_size: u64,
+
+	#[serde(flatten)]
+	other: BTreeMap<String, Value>,
+}
+
+impl Include {
+	pub fn signers(&self) -> &[String] {
+		&self.signers
+	}
+
+	pub fn signers_required(&self) -> u64 {
+		self.signers_required
+	}
+
+	pub fn files_allowed(&self) -> &str {
+		&self.files_allowed
+	}
+
+	pub fn includes_allowed(&self) -> bool {
+		self.includes_allowed
+	}
+
+	pub fn max_size(&self) -> u64 {
+		self.max_size
+	}
+}
+
+/// Builds a new include rule, e.g. to delegate a directory of the site to another signer.
+pub struct IncludeBuilder {
+	include: Include,
+}
+
+impl IncludeBuilder {
+	pub fn new() -> IncludeBuilder {
+		IncludeBuilder {
+			include: Include {
+				signers_required: 1,
+				..Default::default()
+			},
+		}
+	}
+
+	pub fn signer(mut self, address: &str) -> IncludeBuilder {
+		self.include.signers.push(address.to_owned());
+		self
+	}
+
+	pub fn signers_required(mut self, signers_required: u64) -> IncludeBuilder {
+		self.include.signers_required = signers_required;
+		self
+	}
+
+	pub fn files_allowed(mut self, pattern: &str) -> IncludeBuilder {
+		self.include.files_allowed = pattern.to_owned();
+		self
+	}
+
+	pub fn includes_allowed(mut self, includes_allowed: bool) -> IncludeBuilder {
+		self.include.includes_allowed = includes_allowed;
+		self
+	}
+
+	pub fn max_size(mut self, max_size: u64) -> IncludeBuilder {
+		self.include.max_size = max_size;
+		self
+	}
+
+	pub fn build(self) -> Result<Include, ContentError> {
+		if !self.include.files_allowed.is_empty() {
+			Pattern::safe_full(&self.include.files_allowed)?;
+		}
+		if self.include.signers_required as usize > self.include.signers.len() {
+			return Err(ContentError::Schema(format!(
+				"signers_required {} exceeds {} signers",
+				self.include.signers_required,
+				self.include.signers.len()
+			)));
+		}
+		Ok(self.include)
+	}
+}
+
+impl Default for IncludeBuilder {
+	fn default() -> Self {
+		IncludeBuilder::new()
+	}
 }
@@ -0,0 +1,149 @@
+use bitcoin::hashes::{hash160, Hash};
+use bitcoin::secp256k1::recovery::{RecoverableSignature, RecoveryId};
+use bitcoin::secp256k1::{Message, PublicKey, Secp256k1};
+use bitcoin::util::base58;
+use serde::{ser::SerializeSeq, Deserialize, Deserializer, Serialize, Serializer};
+use serde_json::value::RawValue;
+use sha2::{Digest, Sha256};
+
+use crate::{
+    util::sha512sum,
+    verification::{VerifyError, VerifyFailure, VerifyReport},
+};
+
+/// The deprecated `sign` field, the `r` and `s` integers of an ECDSA signature made
+/// before `signs` existed. They are 256-bit, so they are kept as their JSON digits, which
+/// `arbitrary_precision` also keeps in a `Value`.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct LegacySign(Vec<String>);
+
+impl LegacySign {
+    pub fn from_compact(compact: &[u8; 64]) -> LegacySign {
+        LegacySign(vec![
+            decimal_from_bytes(&compact[..32]),
+            decimal_from_bytes(&compact[32..]),
+        ])
+    }
+
+    /// The integers as written in the content.json.
+    pub fn integers(&self) -> &[String] {
+        &self.0
+    }
+
+    /// `r` and `s` as 32 big-endian bytes each, `None` unless they are two 256-bit integers.
+    pub fn to_compact(&self) -> Option<[u8; 64]> {
+        if self.0.len() != 2 {
+            return None;
+        }
+        let mut compact = [0; 64];
+        compact[..32].copy_from_slice(&bytes_from_decimal(&self.0[0])?);
+        compact[32..].copy_from_slice(&bytes_from_decimal(&self.0[1])?);
+        Some(compact)
+    }
+}
+
+impl Serialize for LegacySign {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut seq = serializer.serialize_seq(Some(self.0.len()))?;
+        for integer in &self.0 {
+            let raw = RawValue::from_string(integer.clone()).map_err(serde::ser::Error::custom)?;
+            seq.serialize_element(&raw)?;
+        }
+        seq.end()
+    }
+}
+
+impl<'de> Deserialize<'de> for LegacySign {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let raw: Vec<Box<RawValue>> = Deserialize::deserialize(deserializer)?;
+        let integers = raw.iter().map(|raw| raw.get().to_owned()).collect();
+        Ok(LegacySign(integers))
+    }
+}
+
+fn bytes_from_decimal(decimal: &str) -> Option<[u8; 32]> {
+    if decimal.is_empty() || !decimal.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    let mut bytes = [0u8; 32];
+    for digit in decimal.bytes() {
+        let mut carry = u32::from(digit - b'0');
+        for byte in bytes.iter_mut().rev() {
+            let value = u32::from(*byte) * 10 + carry;
+            *byte = value as u8;
+            carry = value >> 8;
+        }
+        if carry != 0 {
+            return None;
+        }
+    }
+    Some(bytes)
+}
+
+fn decimal_from_bytes(bytes: &[u8]) -> String {
+    let mut bytes = bytes.to_vec();
+    let mut digits = vec![];
+    while bytes.iter().any(|&byte| byte != 0) {
+        let mut remainder = 0u32;
+        for byte in bytes.iter_mut() {
+            let value = (remainder << 8) | u32::from(*byte);
+            *byte = (value / 10) as u8;
+            remainder = value % 10;
+        }
+        digits.push(b'0' + remainder as u8);
+    }
+    if digits.is_empty() {
+        return "0".to_owned();
+    }
+    digits.reverse();
+    String::from_utf8(digits).unwrap_or_default()
+}
+
+/// Address of the uncompressed public key, as the old signing scheme used.
+pub(crate) fn uncompressed_address(pubkey: &PublicKey) -> String {
+    let hash = hash160::Hash::hash(&pubkey.serialize_uncompressed());
+    let mut payload = vec![0];
+    payload.extend_from_slice(&hash[..]);
+    base58::check_encode_slice(&payload)
+}
+
+/// Checks a legacy `sign` like ZeroNet's BitcoinECC did: the public key recovered from
+/// `r` and `s` over the sha256 of `data` must belong to `signer`.
+pub(crate) fn verify_legacy_signature(
+    data: &str,
+    signer: &str,
+    sign: &LegacySign,
+) -> Result<VerifyReport, VerifyError> {
+    let hash = sha512sum(data.as_bytes());
+    let error = |reason| VerifyError {
+        signer: signer.to_owned(),
+        reason,
+        hash: Some(hash.clone()),
+    };
+    let compact = sign
+        .to_compact()
+        .ok_or_else(|| error(VerifyFailure::MalformedSignature))?;
+    let message = Message::from_slice(&Sha256::digest(data.as_bytes()))
+        .map_err(|err| error(VerifyFailure::AddressMismatch(err.to_string())))?;
+    let secp = Secp256k1::verification_only();
+    let mut recovered = vec![];
+    for recovery_id in 0..4 {
+        let pubkey = RecoveryId::from_i32(recovery_id)
+            .and_then(|recovery_id| RecoverableSignature::from_compact(&compact, recovery_id))
+            .and_then(|signature| secp.recover(&message, &signature));
+        if let Ok(pubkey) = pubkey {
+            let address = uncompressed_address(&pubkey);
+            if address == signer {
+                return Ok(VerifyReport {
+                    signer: signer.to_owned(),
+                    hash,
+                });
+            }
+            recovered.push(address);
+        }
+    }
+    Err(error(VerifyFailure::AddressMismatch(format!(
+        "recovered {}",
+        recovered.join(", ")
+    ))))
+}
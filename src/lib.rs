@@ -1,21 +1,47 @@
+pub mod builder;
 pub mod content;
+pub mod diff;
+pub mod error;
 pub mod file;
 pub mod include;
+pub mod legacy;
+pub mod patch;
+pub mod pattern;
+pub mod signer;
+mod surrogate;
+pub mod tree;
 pub mod user_contents;
 mod util;
+pub mod verification;
 mod zeruformatter;
 
 pub use json_filter_sorted::*;
 
+pub use builder::ContentBuilder;
 pub use content::Content;
-pub use file::File;
-pub use include::Include;
-pub use user_contents::UserContents;
+pub use diff::{ContentDiff, MapDiff, UserContentsDiff};
+pub use error::ContentError;
+pub use file::{File, FileError};
+pub use include::{Include, IncludeBuilder};
+pub use legacy::LegacySign;
+pub use patch::{DiffAction, PatchError};
+pub use pattern::Pattern;
+pub use signer::{PrivateKeySigner, Signer};
+pub use tree::{ContentTree, Rules};
+pub use user_contents::{UserContentError, UserContents};
 pub use util::Number;
+pub use verification::{
+    CertError, ThresholdError, ThresholdReport, UpdateError, VerifyError, VerifyFailure,
+    VerifyReport,
+};
 
 #[cfg(test)]
 #[cfg_attr(tarpaulin, ignore)]
 mod tests {
+    use std::cell::RefCell;
+    use std::fs;
+    use std::path::PathBuf;
+
     use serde_bytes::ByteBuf;
 
     use crate::user_contents::PermissionRulesType;
@@ -27,7 +53,7 @@ mod tests {
         let content = Content::from_buf(ByteBuf::from(CONTENT.1.as_bytes())).unwrap();
         let key = CONTENT.0.into();
         let result = content.verify(key);
-        assert!(result);
+        assert!(result.is_ok());
     }
 
     #[test]
@@ -35,7 +61,7 @@ mod tests {
         let content = Content::from_buf(ByteBuf::from(CONTENT_UNICODE.1.as_bytes())).unwrap();
         let key = CONTENT_UNICODE.0.into();
         let result = content.verify(key);
-        assert!(result);
+        assert!(result.is_ok());
     }
 
     #[test]
@@ -44,7 +70,21 @@ mod tests {
             Content::from_buf(ByteBuf::from(CONTENT_UNICODE_UNESCAPED.1.as_bytes())).unwrap();
         let key = CONTENT_UNICODE_UNESCAPED.0.into();
         let result = content.verify(key);
-        assert!(result)
+        assert!(result.is_ok())
+    }
+
+    #[test]
+    fn test_literal_escape_verification() {
+        let content =
+            Content::from_buf(ByteBuf::from(CONTENT_LITERAL_ESCAPE.1.as_bytes())).unwrap();
+        assert_eq!(
+            content.description,
+            r"Type \u00e9 or C:\users\u\ to get \\u"
+        );
+        assert_eq!(content.title, "Literal \u{e9}scapes");
+        let key = CONTENT_LITERAL_ESCAPE.0.into();
+        assert!(content.verify(key).is_ok());
+        assert_eq!(content.raw().unwrap()["description"], content.description);
     }
 
     #[test]
@@ -52,7 +92,7 @@ mod tests {
         let content = Content::from_buf(ByteBuf::from(CONTENT_TEST.1.as_bytes())).unwrap();
         let key = CONTENT_TEST.0.into();
         let result = content.verify(key);
-        assert!(result);
+        assert!(result.is_ok());
     }
 
     #[test]
@@ -60,7 +100,7 @@ mod tests {
         let content = Content::from_buf(ByteBuf::from(CONTENT_DATA_TEST.1.as_bytes())).unwrap();
         let key = CONTENT_DATA_TEST.0.into();
         let result = content.verify(key);
-        assert!(result);
+        assert!(result.is_ok());
         let user_contents = content.user_contents.unwrap();
         assert_eq!(
             user_contents.cert_signers["zeroid.bit"],
@@ -70,8 +110,8 @@ mod tests {
         if let PermissionRulesType::Rules(rules) = permission_rules.clone() {
             assert_eq!(rules.files_allowed, "data.json");
             assert_eq!(rules.files_allowed_optional, ".*\\.(png|jpg|gif)");
-            assert_eq!(rules.max_size, 10000);
-            assert_eq!(rules.max_size_optional, 10000000);
+            assert_eq!(rules.max_size, Some(10000));
+            assert_eq!(rules.max_size_optional, Some(10000000));
             assert_eq!(
                 rules.signers,
                 ["14wgQ4VDDZNoRMFF4yCDuTrBSHmYhL3bet".to_string()]
@@ -81,13 +121,13 @@ mod tests {
         }
         let permission_rules = &user_contents.permission_rules["bitid/.*@zeroid.bit"];
         if let PermissionRulesType::Rules(rules) = permission_rules.clone() {
-            assert_eq!(rules.max_size, 40000);
+            assert_eq!(rules.max_size, Some(40000));
         } else {
             unreachable!();
         }
         let permission_rules = &user_contents.permission_rules["bitmsg/.*@zeroid.bit"];
         if let PermissionRulesType::Rules(rules) = permission_rules.clone() {
-            assert_eq!(rules.max_size, 15000);
+            assert_eq!(rules.max_size, Some(15000));
         } else {
             unreachable!();
         }
@@ -97,12 +137,1302 @@ mod tests {
         }
         let permission_rules = &user_contents.permissions["nofish@zeroid.bit"];
         if let PermissionRulesType::Rules(rules) = permission_rules.clone() {
-            assert_eq!(rules.max_size, 100000);
+            assert_eq!(rules.max_size, Some(100000));
         } else {
             unreachable!();
         }
     }
 
+    #[test]
+    fn test_verification_report() {
+        let content = Content::from_buf(ByteBuf::from(CONTENT_UNICODE.1.as_bytes())).unwrap();
+        let report = content.verify(CONTENT_UNICODE.0.into()).unwrap();
+        assert_eq!(report.signer, CONTENT_UNICODE.0);
+        assert_eq!(report.hash.len(), 64);
+    }
+
+    #[test]
+    fn test_verification_missing_signature() {
+        let content = Content::from_buf(ByteBuf::from(CONTENT.1.as_bytes())).unwrap();
+        let key = "1TeSTvb4w2PWE81S2rEELgmX2GCCExQGT".to_string();
+        if let ContentError::Crypto(err) = content.verify(key.clone()).unwrap_err() {
+            assert_eq!(err.signer, key);
+            assert_eq!(err.reason, VerifyFailure::MissingSignature);
+            assert_eq!(err.hash, None);
+        } else {
+            unreachable!();
+        }
+    }
+
+    #[test]
+    fn test_verification_tampered() {
+        let tampered = CONTENT.1.replace("Bot Hub", "Bot Hub!");
+        let content = Content::from_buf(ByteBuf::from(tampered.as_bytes())).unwrap();
+        if let ContentError::Crypto(err) = content.verify(CONTENT.0.into()).unwrap_err() {
+            assert!(matches!(err.reason, VerifyFailure::AddressMismatch(_)));
+            assert!(err.hash.is_some());
+        } else {
+            unreachable!();
+        }
+    }
+
+    #[test]
+    fn test_verification_malformed_signature() {
+        let mut content = Content::from_buf(ByteBuf::from(CONTENT.1.as_bytes())).unwrap();
+        content
+            .signs
+            .insert(CONTENT.0.to_string(), "not a signature".to_string());
+        if let ContentError::Crypto(err) = content.verify(CONTENT.0.into()).unwrap_err() {
+            assert_eq!(err.reason, VerifyFailure::MalformedSignature);
+        } else {
+            unreachable!();
+        }
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let result = Content::from_buf(ByteBuf::from(vec![b'{', 0xff, b'}']));
+        assert!(matches!(result, Err(ContentError::Utf8(_))));
+
+        let result = Content::from_buf(ByteBuf::from(&b"{\"address\": "[..]));
+        assert!(matches!(result, Err(ContentError::Json(_))));
+
+        let result = Content::from_buf(ByteBuf::from(&b"{\"files\": 5}"[..]));
+        assert!(matches!(result, Err(ContentError::Schema(_))));
+    }
+
+    #[test]
+    fn test_threshold_verification() {
+        let content = Content::from_buf(ByteBuf::from(CONTENT_TEST.1.as_bytes())).unwrap();
+        let valid_signers = vec![CONTENT_TEST.0.to_string()];
+        let report = content.verify_threshold(&valid_signers).unwrap();
+        assert_eq!(report.required, 1);
+        assert_eq!(report.passed.len(), 1);
+        assert!(report.failed.is_empty());
+    }
+
+    #[test]
+    fn test_threshold_verification_unknown_signer() {
+        let content = Content::from_buf(ByteBuf::from(CONTENT_TEST.1.as_bytes())).unwrap();
+        let valid_signers = vec!["1LSxsKfC9S9TVXGGNSM3vPHjyW82jgCX5f".to_string()];
+        let err = content.verify_threshold(&valid_signers).unwrap_err();
+        if let ThresholdError::NotEnoughSignatures(report) = err {
+            assert!(report.passed.is_empty());
+            assert_eq!(report.failed[0].signer, CONTENT_TEST.0);
+            assert_eq!(report.failed[0].reason, VerifyFailure::UnknownSigner);
+        } else {
+            unreachable!();
+        }
+    }
+
+    #[test]
+    fn test_threshold_verification_signs_required() {
+        let mut content = Content::from_buf(ByteBuf::from(CONTENT_TEST.1.as_bytes())).unwrap();
+        content.signs_required = 2;
+        content.signs.insert(
+            "1LSxsKfC9S9TVXGGNSM3vPHjyW82jgCX5f".to_string(),
+            content.signs[CONTENT_TEST.0].clone(),
+        );
+        let valid_signers = vec![
+            CONTENT_TEST.0.to_string(),
+            "1LSxsKfC9S9TVXGGNSM3vPHjyW82jgCX5f".to_string(),
+        ];
+        let err = content.verify_threshold(&valid_signers).unwrap_err();
+        if let ThresholdError::NotEnoughSignatures(report) = err {
+            assert_eq!(report.required, 2);
+            assert_eq!(report.passed.len(), 1);
+            assert_eq!(report.failed.len(), 1);
+        } else {
+            unreachable!();
+        }
+    }
+
+    #[test]
+    fn test_signers_sign_verification() {
+        let content = Content::from_buf(ByteBuf::from(CONTENT.1.as_bytes())).unwrap();
+        assert_eq!(content.signers_data(), format!("1:{}", CONTENT.0));
+        assert!(content.verify_signers_sign().is_ok());
+        assert!(content.verify_root().is_ok());
+
+        let content = Content::from_buf(ByteBuf::from(CONTENT_TEST.1.as_bytes())).unwrap();
+        assert!(content.verify_signers_sign().is_ok());
+    }
+
+    #[test]
+    fn test_signers_sign_extra_signer() {
+        let mut content = Content::from_buf(ByteBuf::from(CONTENT.1.as_bytes())).unwrap();
+        content.signers = vec![SIGNER_KEY.0.to_string()];
+        let err = content.verify_root().unwrap_err();
+        if let ThresholdError::InvalidSignersSign(err) = err {
+            assert_eq!(err.signer, CONTENT.0);
+            assert!(matches!(err.reason, VerifyFailure::AddressMismatch(_)));
+        } else {
+            unreachable!();
+        }
+    }
+
+    #[test]
+    fn test_signers_sign_round_trip() {
+        let mut content = Content::create(SITE_KEY.0.to_string(), 0);
+        content.signers = vec![SIGNER_KEY.0.to_string()];
+        assert!(content.sign_signers("nope".to_string()).is_err());
+        content.signers_sign = content.sign_signers(SITE_KEY.1.to_string()).unwrap();
+        assert!(content.sign("nope".to_string()).is_err());
+        let signature = content.sign(SIGNER_KEY.1.to_string()).unwrap();
+        content.signs.insert(SIGNER_KEY.0.to_string(), signature);
+        let bytes = content::dump(&content).unwrap();
+        let content = Content::from_buf(ByteBuf::from(bytes.as_bytes())).unwrap();
+        let report = content.verify_root().unwrap();
+        assert_eq!(report.passed[0].signer, SIGNER_KEY.0);
+    }
+
+    #[test]
+    fn test_sign_in_place() {
+        let mut content = Content::create(SITE_KEY.0.to_string(), 0);
+        content.title = "My New Site \u{1f601}".to_string();
+        content.modified = 0.into();
+        let buf = content.sign_in_place(SITE_KEY.1).unwrap();
+        assert!(usize::from(content.modified) > 0);
+        assert!(content.verify(SITE_KEY.0.into()).is_ok());
+
+        let content = Content::from_buf(buf).unwrap();
+        assert!(content.verify(SITE_KEY.0.into()).is_ok());
+        assert!(content.verify_signers_sign().is_ok());
+        assert!(content.verify_root().is_ok());
+    }
+
+    #[test]
+    fn test_sign_in_place_invalid_key() {
+        let mut content = Content::create(SITE_KEY.0.to_string(), 0);
+        let result = content.sign_in_place("not a key");
+        assert!(matches!(result, Err(ContentError::Sign(_))));
+    }
+
+    struct MockSigner {
+        signed: RefCell<Vec<Vec<u8>>>,
+        fail: bool,
+    }
+
+    impl MockSigner {
+        fn new(fail: bool) -> MockSigner {
+            MockSigner {
+                signed: RefCell::new(vec![]),
+                fail,
+            }
+        }
+    }
+
+    impl Signer for MockSigner {
+        fn address(&self) -> String {
+            SITE_KEY.0.to_string()
+        }
+
+        fn sign(&self, data: &[u8]) -> Result<String, ContentError> {
+            if self.fail {
+                return Err(ContentError::Sign("keystore is locked".to_string()));
+            }
+            self.signed.borrow_mut().push(data.to_vec());
+            PrivateKeySigner::new(SITE_KEY.1)?.sign(data)
+        }
+    }
+
+    #[test]
+    fn test_sign_in_place_with_signer() {
+        let mut content = Content::create(SITE_KEY.0.to_string(), 0);
+        let signer = MockSigner::new(false);
+        let buf = content.sign_in_place_with(&signer).unwrap();
+        let signed = signer.signed.borrow();
+        assert_eq!(signed.len(), 2);
+        assert_eq!(signed[0], content.signers_data().into_bytes());
+
+        let content = Content::from_buf(buf).unwrap();
+        assert!(content.verify_root().is_ok());
+    }
+
+    #[test]
+    fn test_sign_in_place_with_failing_signer() {
+        let mut content = Content::create(SITE_KEY.0.to_string(), 0);
+        let result = content.sign_in_place_with(&MockSigner::new(true));
+        assert!(matches!(result, Err(ContentError::Sign(_))));
+    }
+
+    fn site_directory(name: &str, files: &[(&str, &str)]) -> PathBuf {
+        let directory =
+            std::env::temp_dir().join(format!("zerucontent-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&directory);
+        for (path, data) in files {
+            let path = directory.join(path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, data).unwrap();
+        }
+        directory
+    }
+
+    #[test]
+    fn test_content_builder() {
+        let directory = site_directory(
+            "builder",
+            &[
+                ("content.json", "{}"),
+                ("index.html", "<html></html>"),
+                ("index.html-old", ""),
+                (".hidden", ""),
+                ("js/all.js", "all"),
+                ("js/other.js", "other"),
+                ("data/zeroblog.db", ""),
+                ("data/img/zero.png", "png"),
+                ("data/optional.txt", "optional"),
+                ("data/users/content.json", "{}"),
+                ("data/users/1abc/data.json", "{}"),
+                ("data/test_include/data.json", "{}"),
+            ],
+        );
+        let mut content = Content::create(SITE_KEY.0.to_string(), 0);
+        content.ignore = "(js|css)/other|data/.*db".to_string();
+        content.optional = "(data/img/zero.*|data/optional.*)".to_string();
+        for inner_path in ["data/users/content.json", "data/test_include/content.json"] {
+            content
+                .includes
+                .insert(inner_path.to_string(), Include::default());
+        }
+        ContentBuilder::from_content(&directory, &content)
+            .build_into(&mut content)
+            .unwrap();
+        fs::remove_dir_all(&directory).unwrap();
+
+        assert_eq!(
+            content.files.keys().collect::<Vec<_>>(),
+            ["index.html", "js/all.js"]
+        );
+        assert_eq!(
+            content.files_optional.keys().collect::<Vec<_>>(),
+            ["data/img/zero.png", "data/optional.txt"]
+        );
+        assert_eq!(
+            content.files["index.html"],
+            File {
+                sha512: util::sha512sum(b"<html></html>"),
+                size: 13,
+            }
+        );
+    }
+
+    #[test]
+    fn test_content_builder_invalid_pattern() {
+        let directory = site_directory("builder-pattern", &[("index.html", "")]);
+        let result = ContentBuilder::new(&directory).ignore("(js").build();
+        let unsafe_result = ContentBuilder::new(&directory).optional("(a+)+").build();
+        fs::remove_dir_all(&directory).unwrap();
+        assert!(matches!(result, Err(ContentError::Pattern(_))));
+        assert!(matches!(unsafe_result, Err(ContentError::Pattern(_))));
+    }
+
+    #[test]
+    fn test_pattern() {
+        let ignore =
+            Pattern::new("((js|css)/(?!all.(js|css))|data/.*db|data/users/.*/.*)").unwrap();
+        assert!(ignore.is_match("js/other.js"));
+        assert!(!ignore.is_match("js/all.js"));
+        assert!(ignore.is_match("data/users/1abc/data.json"));
+        assert!(!ignore.is_match("index.html"));
+        assert!(!Pattern::new("html").unwrap().is_match("index.html"));
+
+        assert!(Pattern::safe("bitid/.*@zeroid.bit").is_ok());
+        for pattern in ["(a+)+", "a*", &".*".repeat(10), &"a".repeat(256)] {
+            assert!(matches!(
+                Pattern::safe(pattern),
+                Err(ContentError::Pattern(_))
+            ));
+        }
+        let files_allowed = Pattern::safe_full("data.json").unwrap();
+        assert!(files_allowed.is_match("data.json"));
+        assert!(!files_allowed.is_match("data.json.bak"));
+    }
+
+    #[test]
+    fn test_verify_file() {
+        let mut content = Content::create(SITE_KEY.0.to_string(), 0);
+        let data = b"<html></html>";
+        let file = File::from_reader(&data[..]).unwrap();
+        content.files.insert("index.html".to_string(), file);
+
+        assert!(content.verify_file("index.html", &data[..]).is_ok());
+        assert!(matches!(
+            content.verify_file("index.html", &b"<html>"[..]),
+            Err(FileError::Size {
+                expected: 13,
+                actual: 6
+            })
+        ));
+        assert!(matches!(
+            content.verify_file("index.html", &b"<body></body>"[..]),
+            Err(FileError::Hash { .. })
+        ));
+        // An endless stream stops being read past the expected size.
+        assert!(matches!(
+            content.verify_file("index.html", std::io::repeat(b'x')),
+            Err(FileError::Size {
+                expected: 13,
+                actual: 14
+            })
+        ));
+        assert!(matches!(
+            content.verify_file("other.html", &data[..]),
+            Err(FileError::Unknown(_))
+        ));
+    }
+
+    #[test]
+    fn test_unknown_fields_round_trip() {
+        let content = Content::from_buf(ByteBuf::from(CONTENT_TEST.1.as_bytes())).unwrap();
+        let dumped: serde_json::Value =
+            serde_json::from_str(&content::dump(&content).unwrap()).unwrap();
+        let include = &dumped["includes"]["data/test_include/content.json"];
+        assert_eq!(include["added"], 1424976057);
+        assert_eq!(include["user_id"], 47);
+        assert_eq!(include["user_name"], "test");
+
+        let mut content = content;
+        let buf = content.sign_in_place(SIGNER_KEY.1).unwrap();
+        let content = Content::from_buf(buf).unwrap();
+        assert!(content.verify(SIGNER_KEY.0.into()).is_ok());
+        let raw = content.raw().unwrap();
+        assert_eq!(
+            raw["includes"]["data/test_include/content.json"]["user_name"],
+            "test"
+        );
+
+        let data = CONTENT_DATA_TEST
+            .1
+            .replace(
+                r#""max_size": 40000"#,
+                r#""max_size": 40000, "note": "bitid""#,
+            )
+            .replace(
+                r#""cert_signers": {"#,
+                r#""cert_help": "help", "cert_signers": {"#,
+            );
+        let original: serde_json::Value = serde_json::from_str(&data).unwrap();
+        let content = Content::from_buf(ByteBuf::from(data.as_bytes())).unwrap();
+        let dumped: serde_json::Value =
+            serde_json::from_str(&content::dump(&content).unwrap()).unwrap();
+        assert_eq!(dumped["user_contents"], original["user_contents"]);
+
+        // Empty maps are kept, Python peers index `permissions` and `permission_rules`.
+        let mut original: serde_json::Value = serde_json::from_str(CONTENT_DATA_TEST.1).unwrap();
+        original["user_contents"]["permissions"] = serde_json::json!({});
+        let data = serde_json::to_string(&original).unwrap();
+        let mut content = Content::from_buf(ByteBuf::from(data.into_bytes())).unwrap();
+        let buf = content.sign_in_place(SITE_KEY.1).unwrap();
+        assert!(String::from_utf8(buf.to_vec())
+            .unwrap()
+            .contains(r#""permissions": {}"#));
+        let content = Content::from_buf(buf).unwrap();
+        assert!(content.verify(SITE_KEY.0.into()).is_ok());
+        assert_eq!(
+            content.raw().unwrap()["user_contents"],
+            original["user_contents"]
+        );
+    }
+
+    #[test]
+    fn test_include_accessors() {
+        let content = Content::from_buf(ByteBuf::from(CONTENT_TEST.1.as_bytes())).unwrap();
+        let include = &content.includes["data/test_include/content.json"];
+        assert_eq!(include.signers(), ["15ik6LeBWnACWfaika1xqGapRZ1zh3JpCo"]);
+        assert_eq!(include.signers_required(), 1);
+        assert_eq!(include.files_allowed(), "data.json");
+        assert!(!include.includes_allowed());
+        assert_eq!(include.max_size(), 20000);
+    }
+
+    #[test]
+    fn test_include_builder() {
+        let mut content = Content::from_buf(ByteBuf::from(CONTENT_TEST.1.as_bytes())).unwrap();
+        let include = IncludeBuilder::new()
+            .signer(SIGNER_KEY.0)
+            .files_allowed("data.json")
+            .max_size(20000)
+            .build()
+            .unwrap();
+        content
+            .includes
+            .insert("data/test_include/content.json".to_string(), include);
+        let include = &content.includes["data/test_include/content.json"];
+        assert_eq!(include.signers(), [SIGNER_KEY.0]);
+        assert_eq!(include.signers_required(), 1);
+
+        let result = IncludeBuilder::new()
+            .signer(SIGNER_KEY.0)
+            .files_allowed("(data")
+            .build();
+        assert!(matches!(result, Err(ContentError::Pattern(_))));
+
+        let result = IncludeBuilder::new()
+            .signer(SIGNER_KEY.0)
+            .signers_required(2)
+            .build();
+        assert!(matches!(result, Err(ContentError::Schema(_))));
+    }
+
+    #[test]
+    fn test_valid_signers() {
+        let root = Content::from_buf(ByteBuf::from(CONTENT_TEST.1.as_bytes())).unwrap();
+        let users = Content::from_buf(ByteBuf::from(CONTENT_DATA_TEST.1.as_bytes())).unwrap();
+        assert_eq!(
+            content::valid_signers("content.json", &root).unwrap(),
+            [CONTENT_TEST.0]
+        );
+        assert_eq!(
+            content::valid_signers("data/test_include/content.json", &root).unwrap(),
+            ["15ik6LeBWnACWfaika1xqGapRZ1zh3JpCo", CONTENT_TEST.0]
+        );
+        assert_eq!(
+            content::valid_signers("data/users/content.json", &root).unwrap(),
+            ["1LSxsKfC9S9TVXGGNSM3vPHjyW82jgCX5f", CONTENT_TEST.0]
+        );
+        assert_eq!(
+            content::valid_signers("data/other/content.json", &root).unwrap(),
+            [CONTENT_TEST.0]
+        );
+        assert_eq!(
+            content::valid_signers(
+                "data/users/1C5sgvWaSgfaTpV5kjBCnCiKtENNMYo69q/content.json",
+                &users
+            )
+            .unwrap(),
+            [
+                "14wgQ4VDDZNoRMFF4yCDuTrBSHmYhL3bet",
+                "1C5sgvWaSgfaTpV5kjBCnCiKtENNMYo69q",
+                CONTENT_TEST.0
+            ]
+        );
+    }
+
+    #[test]
+    fn test_verify_with_parent() {
+        let root = Content::from_buf(ByteBuf::from(CONTENT_TEST.1.as_bytes())).unwrap();
+        let users = Content::from_buf(ByteBuf::from(CONTENT_DATA_TEST.1.as_bytes())).unwrap();
+        assert!(root.verify_with_parent(&root).is_ok());
+        let report = users.verify_with_parent(&root).unwrap();
+        assert_eq!(report.passed[0].signer, CONTENT_TEST.0);
+
+        let mut include = Content::create(CONTENT_TEST.0.to_string(), 0);
+        include.inner_path = "data/test_include/content.json".to_string();
+        include.sign_in_place(SIGNER_KEY.1).unwrap();
+        let err = include.verify_with_parent(&root).unwrap_err();
+        if let ThresholdError::NotEnoughSignatures(report) = err {
+            assert_eq!(report.failed[0].reason, VerifyFailure::UnknownSigner);
+        } else {
+            unreachable!();
+        }
+
+        // Signers of matching permission_rules may sign user content, banned users may not.
+        let mut users = users;
+        let user_contents = users.user_contents.as_mut().unwrap();
+        if let Some(PermissionRulesType::Rules(rules)) =
+            user_contents.permission_rules.get_mut(".*")
+        {
+            rules.signers = vec![SIGNER_KEY.0.to_string()];
+        }
+        let mut user = Content::create(CONTENT_TEST.0.to_string(), 0);
+        user.inner_path = format!("data/users/{}/content.json", SITE_KEY.0);
+        user.sign_in_place(SIGNER_KEY.1).unwrap();
+        assert!(user.verify_with_parent(&users).is_ok());
+        user.sign_in_place(SITE_KEY.1).unwrap();
+        assert!(user.verify_with_parent(&users).is_ok());
+        users
+            .user_contents
+            .as_mut()
+            .unwrap()
+            .permissions
+            .insert(SITE_KEY.0.to_string(), PermissionRulesType::None(false));
+        assert!(matches!(
+            user.verify_with_parent(&users),
+            Err(ThresholdError::NotEnoughSignatures(_))
+        ));
+    }
+
+    fn user_content(cert_user_id: &str, files: &str, files_optional: &str) -> Content {
+        let data = format!(
+            r#"{{"address": "{}", "cert_auth_type": "{}", "cert_user_id": "{}",
+            "files": {{{}}}, "files_optional": {{{}}},
+            "inner_path": "data/users/{}/content.json", "modified": 1470340815}}"#,
+            CONTENT_DATA_TEST.0,
+            cert_user_id.split('/').next().unwrap(),
+            cert_user_id.split('/').nth(1).unwrap(),
+            files,
+            files_optional,
+            SIGNER_KEY.0
+        );
+        Content::from_buf(ByteBuf::from(data.into_bytes())).unwrap()
+    }
+
+    #[test]
+    fn test_user_content_rules() {
+        let users = Content::from_buf(ByteBuf::from(CONTENT_DATA_TEST.1.as_bytes())).unwrap();
+        let user_contents = users.user_contents.unwrap();
+
+        let rules = user_contents
+            .rules_for(&user_content("bitmsg/someone@zeroid.bit", "", ""))
+            .unwrap();
+        assert_eq!(rules.files_allowed, "data.json");
+        assert_eq!(rules.max_size, Some(15000));
+        assert_eq!(rules.max_size_optional, Some(10000000));
+        assert_eq!(
+            rules.signers,
+            ["14wgQ4VDDZNoRMFF4yCDuTrBSHmYhL3bet", SIGNER_KEY.0]
+        );
+
+        let rules = user_contents
+            .rules_for(&user_content("bitid/nofish@zeroid.bit", "", ""))
+            .unwrap();
+        assert_eq!(rules.max_size, Some(100000));
+
+        let rules = user_contents
+            .rules_for(&user_content("web/someone@other.bit", "", ""))
+            .unwrap();
+        assert_eq!(rules.max_size, Some(10000));
+
+        let result = user_contents.rules_for(&user_content("bitid/bad@zeroid.bit", "", ""));
+        assert!(matches!(result, Err(UserContentError::Banned(_))));
+    }
+
+    #[test]
+    fn test_validate_user_content() {
+        let users = Content::from_buf(ByteBuf::from(CONTENT_DATA_TEST.1.as_bytes())).unwrap();
+        let user_contents = users.user_contents.unwrap();
+        let file = |size| format!(r#"{{"sha512": "{}", "size": {}}}"#, "0".repeat(64), size);
+
+        let content = user_content(
+            "bitmsg/someone@zeroid.bit",
+            &format!(r#""data.json": {}"#, file(100)),
+            &format!(r#""avatar.png": {}"#, file(5000)),
+        );
+        assert!(user_contents.validate_user_content(&content).is_ok());
+
+        let content = user_content(
+            "bitmsg/someone@zeroid.bit",
+            &format!(r#""data.json.bak": {}"#, file(100)),
+            "",
+        );
+        assert!(matches!(
+            user_contents.validate_user_content(&content),
+            Err(UserContentError::FileNotAllowed(path)) if path == "data.json.bak"
+        ));
+
+        let content = user_content(
+            "bitmsg/someone@zeroid.bit",
+            "",
+            &format!(r#""avatar.exe": {}"#, file(100)),
+        );
+        assert!(matches!(
+            user_contents.validate_user_content(&content),
+            Err(UserContentError::FileNotAllowed(_))
+        ));
+
+        let content = user_content(
+            "bitmsg/someone@zeroid.bit",
+            &format!(r#""data.json": {}"#, file(20000)),
+            "",
+        );
+        assert!(matches!(
+            user_contents.validate_user_content(&content),
+            Err(UserContentError::TooLarge {
+                max_size: 15000,
+                ..
+            })
+        ));
+
+        let content = user_content(
+            "bitid/nofish@zeroid.bit",
+            &format!(r#""data.json": {}"#, file(20000)),
+            &format!(r#""avatar.png": {}"#, file(20000000)),
+        );
+        assert!(matches!(
+            user_contents.validate_user_content(&content),
+            Err(UserContentError::OptionalTooLarge { .. })
+        ));
+
+        // An explicit 0 is a limit too.
+        let mut user_contents = user_contents;
+        if let Some(PermissionRulesType::Rules(rules)) =
+            user_contents.permission_rules.get_mut(".*")
+        {
+            rules.max_size_optional = Some(0);
+        }
+        let content = user_content(
+            "bitmsg/someone@zeroid.bit",
+            "",
+            &format!(r#""avatar.png": {}"#, file(1)),
+        );
+        assert!(matches!(
+            user_contents.validate_user_content(&content),
+            Err(UserContentError::OptionalTooLarge { max_size: 0, .. })
+        ));
+    }
+
+    #[test]
+    fn test_dumped_size() {
+        // len(json.dumps(content, indent=1)) in Python.
+        let data = r#"{"address": "1TeSTvb4w2PWE81S2rEELgmX2GCCExQGT", "cert_auth_type": "web",
+            "cert_user_id": "nofish@zeroid.bit", "files": {"data.json": {"sha512":
+            "2378ef20379f1db0c3e2a803bfbfda2b2cc6b7c2ec6cbb7c3e3f3c9c6d3f8a17", "size": 161}},
+            "files_optional": {}, "settings": {},
+            "inner_path": "data/users/1C5sgvWaSgfaTpV5kjBCnCiKtENNMYo69q/content.json",
+            "modified": 1470340815.2280, "signs": {"1C5sgvWaSgfaTpV5kjBCnCiKtENNMYo69q":
+            "G25hsrlyTOy8PHKuovKDRC7puoBj/OLIZ3U4OJ01izkhE1BBQ+TOgxX96+HXoZGme2/P4IdEnYjc1rqIZ6O+nFk="},
+            "title": "Caf\u00e9 \ud83d\ude00"}"#;
+        let content = Content::from_buf(ByteBuf::from(data.as_bytes())).unwrap();
+        assert_eq!(content.dumped_size().unwrap(), 577);
+
+        let value = serde_json::json!({"a": [1, {"b": [], "c": 0.5}], "d": {}, "e": "\u{e9}"});
+        let mut buf = vec![];
+        zeruformatter::to_writer_with(
+            &mut buf,
+            &value,
+            zeruformatter::ZeruFormatter::python_indent(1),
+        )
+        .unwrap();
+        assert_eq!(
+            String::from_utf8(buf).unwrap(),
+            "{\n \"a\": [\n  1,\n  {\n   \"b\": [],\n   \"c\": 0.5\n  }\n ],\n \"d\": {},\n \"e\": \"\\u00e9\"\n}"
+        );
+    }
+
+    #[test]
+    fn test_lone_surrogates() {
+        let data = r#"{"address": "1M3BMyg4m5JLD82Be3Z4PSzLv2U8GdJGmc", "files": {}, "ignore": "",
+            "inner_path": "content.json", "modified": 1, "zeronet_version": "",
+            "title": "x\ud83d \ude01😀 󰀀 􏿿"}"#;
+        // json.dumps(json.loads(data), sort_keys=True) in Python.
+        let dumped = r#"{"address": "1M3BMyg4m5JLD82Be3Z4PSzLv2U8GdJGmc", "files": {}, "ignore": "", "inner_path": "content.json", "modified": 1, "title": "x\ud83d \ude01\ud83d\ude00 \udb80\udc00 \udbff\udfff", "zeronet_version": ""}"#;
+        let mut content = Content::from_buf(ByteBuf::from(data.as_bytes())).unwrap();
+        let mut buf = vec![];
+        content.write_canonical(&mut buf).unwrap();
+        assert_eq!(String::from_utf8(buf).unwrap(), dumped);
+        assert_eq!(content.dumped_size().unwrap(), 218);
+
+        let buf = content.sign_in_place(SITE_KEY.1).unwrap();
+        assert!(String::from_utf8(buf.to_vec())
+            .unwrap()
+            .contains(r#""title": "x\ud83d \ude01\ud83d\ude00 \udb80\udc00 \udbff\udfff""#));
+        let content = Content::from_buf(buf).unwrap();
+        assert!(content.verify(SITE_KEY.0.into()).is_ok());
+    }
+
+    #[test]
+    fn test_python_dumps_corpus() {
+        for (signer, data) in [
+            CONTENT,
+            CONTENT_TEST,
+            CONTENT_DATA_TEST,
+            CONTENT_UNICODE,
+            CONTENT_UNICODE_UNESCAPED,
+            CONTENT_LITERAL_ESCAPE,
+        ] {
+            let mut value: serde_json::Value = serde_json::from_str(data).unwrap();
+            let map = value.as_object_mut().unwrap();
+            map.remove("sign");
+            let signs = map.remove("signs").unwrap();
+            let dumped =
+                zeruformatter::to_string_with(&value, zeruformatter::ZeruFormatter::python())
+                    .unwrap();
+            assert!(dumped.is_ascii());
+            let signature = signs[signer].as_str().unwrap();
+            assert!(zeronet_cryptography::verify(dumped.as_bytes(), signer, signature).is_ok());
+        }
+
+        let value = serde_json::json!({
+            "b": "caf\u{e9} \u{1f601}\u{7f}\n\u{1}\\u",
+            "a": [1e16, 0.5, -3],
+        });
+        assert_eq!(
+            zeruformatter::to_string_with(&value, zeruformatter::ZeruFormatter::python()).unwrap(),
+            r#"{"a": [1e+16, 0.5, -3], "b": "caf\u00e9 \ud83d\ude01\u007f\n\u0001\\u"}"#
+        );
+
+        let mut content = Content::create(SITE_KEY.0.to_string(), 0);
+        content.title = "caf\u{e9} \u{1f601}".to_string();
+        let buf = content.sign_in_place(SITE_KEY.1).unwrap();
+        assert!(buf.is_ascii());
+        let content = Content::from_buf(buf).unwrap();
+        assert_eq!(content.title, "caf\u{e9} \u{1f601}");
+        assert!(content.verify(SITE_KEY.0.into()).is_ok());
+    }
+
+    #[test]
+    fn test_legacy_sign() {
+        let content = Content::from_buf(ByteBuf::from(CONTENT.1.as_bytes())).unwrap();
+        let sign = content.legacy_sign().unwrap();
+        assert_eq!(
+            sign.integers(),
+            [
+                "60601328857260736769667767617236149396007806053808183569130735997086722937268",
+                "43661716327244911082383801335054839207111588960552431293232589470692186442781"
+            ]
+        );
+        assert_eq!(LegacySign::from_compact(&sign.to_compact().unwrap()), *sign);
+        let sign_json = format!("[{}, {}]", sign.integers()[0], sign.integers()[1]);
+        assert!(content::dump(&content).unwrap().contains(&sign_json));
+        let raw = content.raw().unwrap();
+        assert_eq!(raw["sign"].to_string().replace(',', ", "), sign_json);
+        let mut buf = vec![];
+        content.write_canonical(&mut buf).unwrap();
+        assert!(String::from_utf8(buf).unwrap().contains(&sign_json));
+        assert!(content.verify(CONTENT.0.into()).is_ok());
+        assert_eq!(content.verify_legacy_sign().unwrap().signer, CONTENT.0);
+
+        let secp = bitcoin::secp256k1::Secp256k1::new();
+        let secret = bitcoin::secp256k1::SecretKey::from_slice(&[7; 32]).unwrap();
+        let address = legacy::uncompressed_address(
+            &bitcoin::secp256k1::PublicKey::from_secret_key(&secp, &secret),
+        );
+        let data = content::dump(Content::create(address.clone(), 0)).unwrap();
+        let digest = <sha2::Sha256 as sha2::Digest>::digest(data.as_bytes());
+        let message = bitcoin::secp256k1::Message::from_slice(&digest).unwrap();
+        let sign = LegacySign::from_compact(&secp.sign(&message, &secret).serialize_compact());
+        let signed = format!(
+            r#"{{"sign": [{}, {}], {}"#,
+            sign.integers()[0],
+            sign.integers()[1],
+            &data[1..]
+        );
+        let legacy = Content::from_buf(ByteBuf::from(signed.as_bytes())).unwrap();
+        assert_eq!(legacy.legacy_sign(), Some(&sign));
+        assert_eq!(legacy.verify_legacy_sign().unwrap().signer, address);
+
+        let tampered = signed.replace(&format!(r#""title": "{}""#, address), r#""title": "x""#);
+        let tampered = Content::from_buf(ByteBuf::from(tampered.into_bytes())).unwrap();
+        if let ContentError::Crypto(err) = tampered.verify_legacy_sign().unwrap_err() {
+            assert!(matches!(err.reason, VerifyFailure::AddressMismatch(_)));
+        } else {
+            unreachable!();
+        }
+        if let ContentError::Crypto(err) = Content::create(address, 0)
+            .verify_legacy_sign()
+            .unwrap_err()
+        {
+            assert_eq!(err.reason, VerifyFailure::MissingSignature);
+        } else {
+            unreachable!();
+        }
+    }
+
+    #[test]
+    fn test_number() {
+        let float = Number::from(1471656205.079839);
+        let integer = Number::from(1471656205u64);
+        assert!(float > integer);
+        assert!(integer < float);
+        assert_eq!(Number::Integer(1), Number::Float(1.0));
+        assert_eq!(
+            [float, integer].iter().max().unwrap().as_f64(),
+            1471656205.079839
+        );
+        assert!(Number::Float(f64::NAN) > Number::Integer(usize::MAX));
+
+        // 2^53 + 1 is not a float, so it must not equal the float 2^53.
+        let large = 1usize << 53;
+        assert_eq!(Number::Integer(large), Number::Float(large as f64));
+        assert!(Number::Integer(large + 1) > Number::Float(large as f64));
+        assert!(Number::Float(large as f64) < Number::Integer(large + 1));
+        assert!(Number::Integer(large + 1) < Number::Float(large as f64 + 2.0));
+        assert!(Number::Integer(usize::MAX) < Number::Float(f64::INFINITY));
+        assert!(Number::Integer(usize::MAX) < Number::Float(1e30));
+        assert!(Number::Integer(0) > Number::Float(-0.5));
+        assert_eq!(Number::Integer(0), Number::Float(-0.0));
+        assert!(Number::Integer(1) < Number::Float(1.5));
+        assert!(Number::Integer(2) > Number::Float(1.5));
+
+        for (value, repr) in [
+            (1471656205.079839, "1471656205.079839"),
+            (1470340815.0, "1470340815.0"),
+            (0.0001, "0.0001"),
+            (0.00001, "1e-05"),
+            (1.5e16, "1.5e+16"),
+            (1e100, "1e+100"),
+            (-2.5, "-2.5"),
+        ] {
+            assert_eq!(Number::Float(value).to_string(), repr);
+        }
+
+        let time = std::time::SystemTime::UNIX_EPOCH + std::time::Duration::from_millis(1500);
+        assert_eq!(Number::from(time), Number::Integer(1));
+
+        let mut content = Content::create(SITE_KEY.0.to_string(), 0);
+        content.modified = Number::Float(1e16);
+        assert!(content::dump(&content)
+            .unwrap()
+            .contains(r#""modified": 1e+16"#));
+
+        // Literals that are not the shortest repr still parse, and dump as Python would.
+        for (literal, repr) in [("1470340815.2280", "1470340815.228"), ("1E3", "1000.0")] {
+            let data = format!(
+                r#"{{"address": "{}", "modified": {}}}"#,
+                SITE_KEY.0, literal
+            );
+            let content = Content::from_buf(ByteBuf::from(data.into_bytes())).unwrap();
+            assert_eq!(content.modified.to_string(), repr);
+            assert!(content::dump(&content)
+                .unwrap()
+                .contains(&format!(r#""modified": {}"#, repr)));
+        }
+    }
+
+    #[test]
+    fn test_check_update() {
+        let accepted = Content::from_buf(ByteBuf::from(CONTENT_DATA_TEST.1.as_bytes())).unwrap();
+        let now = std::time::SystemTime::UNIX_EPOCH + std::time::Duration::from_secs(1470340815);
+        let mut content = accepted.clone();
+        assert!(!content.is_newer_than(&accepted));
+        assert!(matches!(
+            content.check_update(Some(&accepted), now),
+            Err(UpdateError::NotNewer { .. })
+        ));
+        assert!(content.check_update(None, now).is_ok());
+
+        content.modified = Number::Integer(1470340816);
+        assert!(content.is_newer_than(&accepted));
+        assert!(content.check_update(Some(&accepted), now).is_ok());
+        content.modified = Number::Integer(1470340815);
+        assert!(!content.is_newer_than(&accepted));
+
+        content.modified = Number::Integer(1470340815 + 2 * 60 * 60 * 24);
+        assert!(matches!(
+            content.check_update(Some(&accepted), now),
+            Err(UpdateError::FarFuture { .. })
+        ));
+    }
+
+    #[test]
+    fn test_content_diff() {
+        let old = Content::from_buf(ByteBuf::from(CONTENT_TEST.1.as_bytes())).unwrap();
+        assert!(old.diff(&old).is_empty());
+
+        let mut content = old.clone();
+        content.files.get_mut("data/data.json").unwrap().size += 1;
+        content.files.remove("data-default/data.json");
+        content.files.insert(
+            "new.html".to_string(),
+            File {
+                sha512: util::sha512sum(b"<html></html>"),
+                size: 13,
+            },
+        );
+        content
+            .files_optional
+            .insert("video.mp4".to_string(), File::default());
+        content
+            .includes
+            .insert("data/other/content.json".to_string(), Include::default());
+        content.title = "New title".to_string();
+        content.modified = Number::Integer(1470340816);
+        content.signs.clear();
+        let mut user_contents = UserContents::default();
+        user_contents.permissions.insert(
+            "bad@zeroid.bit".to_string(),
+            PermissionRulesType::None(false),
+        );
+        user_contents.archived_before = 1470340816;
+        content.user_contents = Some(user_contents);
+
+        let diff = content.diff(&old);
+        assert_eq!(diff.files.added, ["new.html"]);
+        assert_eq!(diff.files.removed, ["data-default/data.json"]);
+        assert_eq!(diff.files.modified, ["data/data.json"]);
+        assert_eq!(
+            diff.files.changed().collect::<Vec<_>>(),
+            ["new.html", "data/data.json"]
+        );
+        assert_eq!(diff.files_optional.added, ["video.mp4"]);
+        assert_eq!(diff.includes.added, ["data/other/content.json"]);
+        assert_eq!(diff.user_contents.permissions.added, ["bad@zeroid.bit"]);
+        assert_eq!(diff.user_contents.fields, ["archived_before"]);
+        assert_eq!(diff.fields, ["modified", "title"]);
+
+        let reverse = old.diff(&content);
+        assert_eq!(reverse.files.added, ["data-default/data.json"]);
+        assert_eq!(reverse.files.removed, ["new.html"]);
+        assert_eq!(
+            reverse.user_contents.permissions.removed,
+            ["bad@zeroid.bit"]
+        );
+    }
+
+    #[test]
+    fn test_update_diff() {
+        let old = b"{\n \"title\": \"Old\",\n \"posts\": [\n  1,\n  2\n ]\n}";
+        let new = b"{\n \"title\": \"New\",\n \"posts\": [\n  1,\n  2,\n  3\n ]\n}\n";
+        let actions = patch::diff(old, new, Some(patch::DIFF_LIMIT)).unwrap();
+        // As ZeroNet's Diff.diff gives for the same files.
+        let expected: Vec<DiffAction> = serde_json::from_str(
+            r#"[["=", 2], ["-", 17], ["+", [" \"title\": \"New\",\n"]], ["=", 17], ["-", 4],
+            ["+", ["  2,\n", "  3\n"]], ["=", 3], ["-", 1], ["+", ["}\n"]]]"#,
+        )
+        .unwrap();
+        assert_eq!(actions, expected);
+        assert_eq!(patch::patch(old, &actions).unwrap(), new);
+        assert!(matches!(
+            patch::diff(old, new, Some(10)),
+            Err(PatchError::LimitReached {
+                size: 17,
+                limit: 10
+            })
+        ));
+        assert!(matches!(
+            patch::patch(old, &[DiffAction::Equal(old.len() + 1)]),
+            Err(PatchError::OutOfRange { .. })
+        ));
+        assert!(serde_json::from_str::<DiffAction>(r#"["?", 1]"#).is_err());
+
+        // Long files leave out popular lines like difflib's autojunk.
+        let old: Vec<u8> = (0..300)
+            .flat_map(|i| match i % 3 {
+                0 => format!("line {}\n", i).into_bytes(),
+                _ => b"x\n".to_vec(),
+            })
+            .collect();
+        let mut new = String::from_utf8(old.clone())
+            .unwrap()
+            .replace("line 150\n", "changed\n")
+            .into_bytes();
+        new.extend_from_slice(b"tail");
+        let actions = patch::diff(&old, &new, None).unwrap();
+        assert_eq!(
+            serde_json::to_value(&actions).unwrap(),
+            serde_json::json!([
+                ["=", 612],
+                ["-", 9],
+                ["+", [b"changed\n"]],
+                ["=", 641],
+                ["+", [b"tail"]]
+            ])
+        );
+
+        let mut content = Content::create(SITE_KEY.0.to_string(), 0);
+        content.files.insert(
+            "data.json".to_string(),
+            File::from_reader(&new[..]).unwrap(),
+        );
+        assert_eq!(
+            content.patch_file("data.json", &old, &actions).unwrap(),
+            new
+        );
+        assert!(matches!(
+            content.patch_file("data.json", &old, &actions[..4]),
+            Err(PatchError::File(FileError::Size { .. }))
+        ));
+        assert!(matches!(
+            content.patch_file("other.json", &old, &actions),
+            Err(PatchError::File(FileError::Unknown(_)))
+        ));
+    }
+
+    #[test]
+    fn test_archived_users() {
+        let users = Content::from_buf(ByteBuf::from(CONTENT_DATA_TEST.1.as_bytes())).unwrap();
+        let mut user_contents = users.user_contents.unwrap();
+        let content = user_content("bitmsg/someone@zeroid.bit", "", "");
+        assert!(!user_contents.is_archived(SIGNER_KEY.0, &content.modified));
+        assert!(user_contents.check_archived(&content).is_ok());
+
+        user_contents.archive(SIGNER_KEY.0);
+        assert!(user_contents.is_archived(SIGNER_KEY.0, &content.modified));
+        assert!(!user_contents.is_archived(SITE_KEY.0, &content.modified));
+        assert!(matches!(
+            user_contents.validate_user_content(&content),
+            Err(UserContentError::Archived(user_dir)) if user_dir == SIGNER_KEY.0
+        ));
+
+        let mut user_contents = UserContents::default();
+        user_contents.archived.insert(SITE_KEY.0.to_string(), 1000);
+        user_contents.archive_before(1470340815);
+        assert!(user_contents.archived.is_empty());
+        assert!(user_contents.is_archived(SIGNER_KEY.0, &Number::Float(1470340815.0)));
+        assert!(!user_contents.is_archived(SIGNER_KEY.0, &Number::Float(1470340815.5)));
+    }
+
+    #[test]
+    fn test_verify_cert() {
+        let users = Content::from_buf(ByteBuf::from(CONTENT_DATA_TEST.1.as_bytes())).unwrap();
+        let mut user_contents = users.user_contents.unwrap();
+        user_contents
+            .cert_signers
+            .insert("zeroid.bit".to_string(), vec![SIGNER_KEY.0.to_string()]);
+        let mut content = user_content("web/nofish@zeroid.bit", "", "");
+        assert_eq!(content.cert_user_id, "nofish@zeroid.bit");
+        assert_eq!(
+            content.cert_data(SITE_KEY.0),
+            format!("{}#web/nofish", SITE_KEY.0)
+        );
+        content.cert_sign =
+            zeronet_cryptography::sign(content.cert_data(SITE_KEY.0).as_bytes(), SIGNER_KEY.1)
+                .unwrap();
+        assert!(content.verify_cert(SITE_KEY.0, &user_contents).is_ok());
+        assert!(matches!(
+            content.verify_cert(SIGNER_KEY.0, &user_contents),
+            Err(CertError::Signature(_))
+        ));
+
+        content.cert_user_id = format!("nofish@{}", SIGNER_KEY.0);
+        assert!(matches!(
+            content.verify_cert(SITE_KEY.0, &user_contents),
+            Err(CertError::UnknownCertSigner(_))
+        ));
+        user_contents.cert_signers_pattern = "1Bdh".to_string();
+        content.cert_sign =
+            zeronet_cryptography::sign(content.cert_data(SITE_KEY.0).as_bytes(), SIGNER_KEY.1)
+                .unwrap();
+        assert!(content.verify_cert(SITE_KEY.0, &user_contents).is_ok());
+
+        content.cert_user_id = "nofish".to_string();
+        assert!(matches!(
+            content.verify_cert(SITE_KEY.0, &user_contents),
+            Err(CertError::InvalidUserId(_))
+        ));
+        content.cert_user_id = String::new();
+        assert!(matches!(
+            content.verify_cert(SITE_KEY.0, &user_contents),
+            Err(CertError::MissingCert)
+        ));
+        assert!(content
+            .verify_cert(SITE_KEY.0, &UserContents::default())
+            .is_ok());
+    }
+
+    fn content_tree() -> ContentTree {
+        let root = Content::from_buf(ByteBuf::from(CONTENT_TEST.1.as_bytes())).unwrap();
+        let mut tree = ContentTree::new(root);
+        let users = Content::from_buf(ByteBuf::from(CONTENT_DATA_TEST.1.as_bytes())).unwrap();
+        tree.insert("data/users/content.json", users);
+        let mut include = Content::create(CONTENT_TEST.0.to_string(), 0);
+        include.inner_path = "data/test_include/content.json".to_string();
+        include
+            .files
+            .insert("data.json".to_string(), File::default());
+        tree.insert("data/test_include/content.json", include);
+        tree
+    }
+
+    #[test]
+    fn test_content_tree_governing() {
+        let tree = content_tree();
+        assert_eq!(tree.governing("index.html").unwrap(), "content.json");
+        assert_eq!(
+            tree.governing("data/test_include/data.json").unwrap(),
+            "data/test_include/content.json"
+        );
+        assert_eq!(
+            tree.governing("data/users/1abc/data.json").unwrap(),
+            "data/users/1abc/content.json"
+        );
+        assert_eq!(
+            tree.governing("data/users/1abc/mycontent.json").unwrap(),
+            "data/users/1abc/content.json"
+        );
+        assert_eq!(
+            tree.governing("data/users/content.json").unwrap(),
+            "data/users/content.json"
+        );
+        assert_eq!(
+            tree.governing("data/users/users.json").unwrap(),
+            "data/users/content.json"
+        );
+    }
+
+    #[test]
+    fn test_content_tree_rules() {
+        let tree = content_tree();
+        let rules = tree.rules("index.html").unwrap();
+        assert_eq!(rules.signers, [CONTENT_TEST.0]);
+        assert!(rules.includes_allowed);
+
+        let rules = tree.rules("data/test_include/data.json").unwrap();
+        assert_eq!(
+            rules.chain,
+            ["content.json", "data/test_include/content.json"]
+        );
+        assert_eq!(
+            rules.signers,
+            ["15ik6LeBWnACWfaika1xqGapRZ1zh3JpCo", CONTENT_TEST.0]
+        );
+        assert_eq!(rules.files_allowed, "data.json");
+        assert_eq!(rules.max_size, Some(20000));
+        assert!(!rules.includes_allowed);
+
+        let rules = tree.rules("data/users/content.json").unwrap();
+        assert_eq!(
+            rules.signers,
+            ["1LSxsKfC9S9TVXGGNSM3vPHjyW82jgCX5f", CONTENT_TEST.0]
+        );
+
+        let rules = tree.rules("data/users/1abc/content.json").unwrap();
+        assert_eq!(
+            rules.chain,
+            [
+                "content.json",
+                "data/users/content.json",
+                "data/users/1abc/content.json"
+            ]
+        );
+        assert_eq!(
+            rules.signers,
+            ["14wgQ4VDDZNoRMFF4yCDuTrBSHmYhL3bet", "1abc", CONTENT_TEST.0]
+        );
+        assert_eq!(rules.files_allowed, "data.json");
+        assert_eq!(rules.max_size, Some(10000));
+        assert!(!rules.includes_allowed);
+        assert_eq!(rules.user_contents.unwrap(), "data/users/content.json");
+        assert_eq!(
+            tree.rules("data/users/1abc/data.json")
+                .unwrap()
+                .content_inner_path,
+            "data/users/1abc/content.json"
+        );
+        assert_eq!(
+            tree.rules("data/users/1abc/mycontent.json")
+                .unwrap()
+                .content_inner_path,
+            "data/users/1abc/content.json"
+        );
+
+        // A loaded user content.json is matched by its cert.
+        let mut tree = content_tree();
+        let user_inner_path = format!("data/users/{}/content.json", SIGNER_KEY.0);
+        tree.insert(
+            &user_inner_path,
+            user_content("bitid/someone@zeroid.bit", "", ""),
+        );
+        let rules = tree.rules(&user_inner_path).unwrap();
+        assert_eq!(rules.max_size, Some(40000));
+        assert_eq!(
+            rules.signers,
+            [
+                "14wgQ4VDDZNoRMFF4yCDuTrBSHmYhL3bet",
+                SIGNER_KEY.0,
+                CONTENT_TEST.0
+            ]
+        );
+        tree.insert(
+            &user_inner_path,
+            user_content("bitid/bad@zeroid.bit", "", ""),
+        );
+        assert!(tree.rules(&user_inner_path).is_none());
+
+        assert!(tree.rules("data/other/content.json").is_none());
+    }
+
+    #[test]
+    fn test_content_tree_load() {
+        let directory = site_directory(
+            "tree",
+            &[
+                ("content.json", CONTENT_TEST.1),
+                ("data/users/content.json", CONTENT_DATA_TEST.1),
+            ],
+        );
+        let tree = ContentTree::load(&directory).unwrap();
+        fs::remove_dir_all(&directory).unwrap();
+        assert_eq!(tree.root().unwrap().address, CONTENT_TEST.0);
+        assert!(tree.get("data/users/content.json").is_some());
+        assert_eq!(
+            tree.governing("data/users/1abc/data.json").unwrap(),
+            "data/users/1abc/content.json"
+        );
+    }
+
+    #[test]
+    #[ignore]
+    fn bench_from_buf() {
+        use std::time::Instant;
+
+        const ITERATIONS: u32 = 10000;
+        for (name, fixture) in [
+            ("CONTENT", CONTENT),
+            ("CONTENT_UNICODE", CONTENT_UNICODE),
+            ("CONTENT_UNICODE_UNESCAPED", CONTENT_UNICODE_UNESCAPED),
+            ("CONTENT_TEST", CONTENT_TEST),
+            ("CONTENT_DATA_TEST", CONTENT_DATA_TEST),
+        ] {
+            let buf = ByteBuf::from(fixture.1.as_bytes());
+            let start = Instant::now();
+            for _ in 0..ITERATIONS {
+                Content::from_buf(buf.clone()).unwrap();
+            }
+            println!("{}: {:?}/iter", name, start.elapsed() / ITERATIONS);
+        }
+    }
+
+    fn content_with_files(count: usize) -> Content {
+        let mut content = Content::create(SITE_KEY.0.to_string(), 0);
+        for i in 0..count {
+            let file = File {
+                sha512: util::sha512sum(i.to_string().as_bytes()),
+                size: i,
+            };
+            content
+                .files
+                .insert(format!("data/{}.json", i), file.clone());
+            if i % 2 == 0 {
+                content
+                    .files_optional
+                    .insert(format!("optional/{}.mp4", i), file);
+            }
+        }
+        content
+    }
+
+    #[test]
+    fn test_write_canonical() {
+        let mut content = content_with_files(500);
+        content.sign_in_place(SITE_KEY.1).unwrap();
+        let mut buf = vec![];
+        content.write_canonical(&mut buf).unwrap();
+        assert_eq!(buf, content::dump(&content).unwrap().as_bytes());
+
+        let content = Content::create(SITE_KEY.0.to_string(), 0);
+        let mut buf = vec![];
+        content.write_canonical(&mut buf).unwrap();
+        assert_eq!(buf, content::dump(&content).unwrap().as_bytes());
+
+        /// Fails once `remaining` bytes are written.
+        struct FailingWriter {
+            remaining: usize,
+        }
+
+        impl std::io::Write for FailingWriter {
+            fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+                if self.remaining == 0 {
+                    return Err(std::io::ErrorKind::WriteZero.into());
+                }
+                let written = buf.len().min(self.remaining);
+                self.remaining -= written;
+                Ok(written)
+            }
+
+            fn flush(&mut self) -> std::io::Result<()> {
+                Ok(())
+            }
+        }
+
+        // The first write is the opening brace, the next ones go through serde_json.
+        for remaining in [0, 1, 10] {
+            let result = content.write_canonical(FailingWriter { remaining });
+            assert!(matches!(result, Err(ContentError::Io(_))), "{:?}", result);
+        }
+    }
+
+    const SITE_KEY: (&str, &str) = (
+        "1M3BMyg4m5JLD82Be3Z4PSzLv2U8GdJGmc",
+        "5JkC2hgWX1zqi5FMDuRwu9STpRk2tvfuY22Ac3HDMY2BWn1ofJT",
+    );
+
+    const SIGNER_KEY: (&str, &str) = (
+        "1BdhS5EbEEC9AqZRjHnJvVkSXkCmeP21uk",
+        "5JkC2hgWX1zqi5FMDuRwu9STpRk2mrrsREn6E2ZPNvkzDyvX7P3",
+    );
+
     const CONTENT: (&str, &str) = (
         "1JUDmCT4UCSdnPsJAHBoXNkDS61Y31Ue52",
         r#"
@@ -180,6 +1510,24 @@ mod tests {
 		}"#,
     );
 
+    const CONTENT_LITERAL_ESCAPE: (&str, &str) = (
+        "1M3BMyg4m5JLD82Be3Z4PSzLv2U8GdJGmc",
+        r#"{
+		"address": "1M3BMyg4m5JLD82Be3Z4PSzLv2U8GdJGmc",
+		"description": "Type \\u00e9 or C:\\users\\u\\ to get \\\\u",
+		"files": {},
+		"ignore": "",
+		"inner_path": "content.json",
+		"modified": 1792315766,
+		"postmessage_nonce_security": true,
+		"signers_sign": "G5UKDJ/nT/BFaZ+2Vi5f3r+PSy/wqxzazaubZZqD8/UaIqkMonGsJnWH3Fi8Lx3FEg+X3D0ebFNfG9gjUz8/NBA=",
+		"signs": {"1M3BMyg4m5JLD82Be3Z4PSzLv2U8GdJGmc": "HItZ9PMvOGFMnVPpAUu0HecM6qwcJMbuFWZ9EhLA01iWYsWCnAZyaptz5Tdeb9jTNdye32pqCtYuHmEz+5mPvWM="},
+		"signs_required": 1,
+		"title": "Literal \u00e9scapes",
+		"zeronet_version": ""
+		}"#,
+    );
+
     const CONTENT_TEST: (&str, &str) = (
         "1TeSTvb4w2PWE81S2rEELgmX2GCCExQGT",
         r#"{
@@ -0,0 +1,297 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt::{self, Display};
+
+use serde::{de, ser::SerializeTuple, Deserialize, Deserializer, Serialize, Serializer};
+use serde_bytes::ByteBuf;
+
+use crate::file::FileError;
+
+/// Most bytes a diff may insert before it is not worth sending, as in ZeroNet's getDiffs.
+pub const DIFF_LIMIT: usize = 30 * 1024;
+
+/// An action of a ZeroNet update diff, `["=", n]`, `["-", n]` or `["+", lines]`, where
+/// `n` counts bytes of the old file.
+#[derive(Debug, Clone, PartialEq)]
+pub enum DiffAction {
+    /// Copies `n` bytes of the old file.
+    Equal(usize),
+    /// Skips `n` bytes of the old file.
+    Delete(usize),
+    /// Writes the lines.
+    Insert(Vec<ByteBuf>),
+}
+
+#[derive(Debug)]
+pub enum PatchError {
+    /// The diff inserts more than the limit, so the file should be sent whole.
+    LimitReached { size: usize, limit: usize },
+    /// An action reads past the end of the old file.
+    OutOfRange {
+        position: usize,
+        length: usize,
+        size: usize,
+    },
+    /// The patched file does not match its entry in the content.json.
+    File(FileError),
+}
+
+impl Display for PatchError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PatchError::LimitReached { size, limit } => {
+                write!(f, "diff limit reached: {} > {}", size, limit)
+            }
+            PatchError::OutOfRange {
+                position,
+                length,
+                size,
+            } => write!(
+                f,
+                "action of {} bytes at {} is past the end of {} bytes",
+                length, position, size
+            ),
+            PatchError::File(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl Error for PatchError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            PatchError::File(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<FileError> for PatchError {
+    fn from(err: FileError) -> Self {
+        PatchError::File(err)
+    }
+}
+
+impl Serialize for DiffAction {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut tuple = serializer.serialize_tuple(2)?;
+        match self {
+            DiffAction::Equal(length) => {
+                tuple.serialize_element("=")?;
+                tuple.serialize_element(length)?;
+            }
+            DiffAction::Delete(length) => {
+                tuple.serialize_element("-")?;
+                tuple.serialize_element(length)?;
+            }
+            DiffAction::Insert(lines) => {
+                tuple.serialize_element("+")?;
+                tuple.serialize_element(lines)?;
+            }
+        }
+        tuple.end()
+    }
+}
+
+impl<'de> Deserialize<'de> for DiffAction {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct ActionVisitor;
+
+        impl<'de> de::Visitor<'de> for ActionVisitor {
+            type Value = DiffAction;
+
+            fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                write!(f, "a diff action such as [\"=\", 10]")
+            }
+
+            fn visit_seq<A: de::SeqAccess<'de>>(self, mut seq: A) -> Result<DiffAction, A::Error> {
+                // Python peers may send the action as bytes rather than a string.
+                let action: ByteBuf = seq
+                    .next_element()?
+                    .ok_or_else(|| de::Error::invalid_length(0, &self))?;
+                let action = match &action[..] {
+                    b"=" => seq.next_element()?.map(DiffAction::Equal),
+                    b"-" => seq.next_element()?.map(DiffAction::Delete),
+                    b"+" => seq.next_element()?.map(DiffAction::Insert),
+                    other => {
+                        return Err(de::Error::custom(format!(
+                            "unknown diff action: {}",
+                            String::from_utf8_lossy(other)
+                        )))
+                    }
+                };
+                action.ok_or_else(|| de::Error::invalid_length(1, &self))
+            }
+        }
+
+        deserializer.deserialize_seq(ActionVisitor)
+    }
+}
+
+/// Line diff from `old` to `new` like ZeroNet's `Diff.diff`, using the opcodes of Python's
+/// `difflib.SequenceMatcher`. Fails once more than `limit` bytes are inserted.
+pub fn diff(old: &[u8], new: &[u8], limit: Option<usize>) -> Result<Vec<DiffAction>, PatchError> {
+    let old_lines = lines(old);
+    let new_lines = lines(new);
+    let sum_len = |lines: &[&[u8]]| lines.iter().map(|line| line.len()).sum::<usize>();
+    let mut actions = vec![];
+    let mut size = 0;
+    let (mut i, mut j) = (0, 0);
+    for (block_i, block_j, block_size) in matching_blocks(&old_lines, &new_lines) {
+        if i < block_i {
+            actions.push(DiffAction::Delete(sum_len(&old_lines[i..block_i])));
+        }
+        if j < block_j {
+            let inserted = &new_lines[j..block_j];
+            size += sum_len(inserted);
+            actions.push(DiffAction::Insert(
+                inserted.iter().map(|line| ByteBuf::from(*line)).collect(),
+            ));
+        }
+        if let Some(limit) = limit {
+            if size > limit {
+                return Err(PatchError::LimitReached { size, limit });
+            }
+        }
+        i = block_i + block_size;
+        j = block_j + block_size;
+        if block_size > 0 {
+            actions.push(DiffAction::Equal(sum_len(&old_lines[block_i..i])));
+        }
+    }
+    Ok(actions)
+}
+
+/// Applies a diff to `old` like ZeroNet's `Diff.patch`.
+pub fn patch(old: &[u8], actions: &[DiffAction]) -> Result<Vec<u8>, PatchError> {
+    let mut new = Vec::with_capacity(old.len());
+    let mut position = 0;
+    for action in actions {
+        match action {
+            DiffAction::Equal(length) => {
+                let end = advance(old, position, *length)?;
+                new.extend_from_slice(&old[position..end]);
+                position = end;
+            }
+            DiffAction::Delete(length) => position = advance(old, position, *length)?,
+            DiffAction::Insert(lines) => {
+                for line in lines {
+                    new.extend_from_slice(line);
+                }
+            }
+        }
+    }
+    Ok(new)
+}
+
+fn advance(old: &[u8], position: usize, length: usize) -> Result<usize, PatchError> {
+    position
+        .checked_add(length)
+        .filter(|end| *end <= old.len())
+        .ok_or(PatchError::OutOfRange {
+            position,
+            length,
+            size: old.len(),
+        })
+}
+
+/// Lines with their `\n`, as iterating a Python file yields them.
+fn lines(data: &[u8]) -> Vec<&[u8]> {
+    data.split_inclusive(|&byte| byte == b'\n').collect()
+}
+
+/// `SequenceMatcher(None, a, b).get_matching_blocks()`, with its autojunk heuristic, as
+/// `(i, j, size)` triples ending with `(a.len(), b.len(), 0)`.
+fn matching_blocks(a: &[&[u8]], b: &[&[u8]]) -> Vec<(usize, usize, usize)> {
+    let mut b2j: HashMap<&[u8], Vec<usize>> = HashMap::new();
+    for (j, line) in b.iter().enumerate() {
+        b2j.entry(*line).or_default().push(j);
+    }
+    if b.len() >= 200 {
+        let popular = b.len() / 100 + 1;
+        b2j.retain(|_, indices| indices.len() <= popular);
+    }
+
+    let mut blocks = vec![];
+    let mut queue = vec![(0, a.len(), 0, b.len())];
+    while let Some((alo, ahi, blo, bhi)) = queue.pop() {
+        let (i, j, size) = longest_match(a, b, &b2j, (alo, ahi, blo, bhi));
+        if size > 0 {
+            blocks.push((i, j, size));
+            if alo < i && blo < j {
+                queue.push((alo, i, blo, j));
+            }
+            if i + size < ahi && j + size < bhi {
+                queue.push((i + size, ahi, j + size, bhi));
+            }
+        }
+    }
+    blocks.sort_unstable();
+
+    let mut collapsed = vec![];
+    let (mut i1, mut j1, mut k1) = (0, 0, 0);
+    for (i2, j2, k2) in blocks {
+        if i1 + k1 == i2 && j1 + k1 == j2 {
+            k1 += k2;
+        } else {
+            if k1 > 0 {
+                collapsed.push((i1, j1, k1));
+            }
+            i1 = i2;
+            j1 = j2;
+            k1 = k2;
+        }
+    }
+    if k1 > 0 {
+        collapsed.push((i1, j1, k1));
+    }
+    collapsed.push((a.len(), b.len(), 0));
+    collapsed
+}
+
+/// `SequenceMatcher.find_longest_match` without junk, returning the earliest longest block.
+fn longest_match(
+    a: &[&[u8]],
+    b: &[&[u8]],
+    b2j: &HashMap<&[u8], Vec<usize>>,
+    (alo, ahi, blo, bhi): (usize, usize, usize, usize),
+) -> (usize, usize, usize) {
+    let (mut best_i, mut best_j, mut best_size) = (alo, blo, 0);
+    let mut j2len: HashMap<usize, usize> = HashMap::new();
+    for (i, line) in a.iter().enumerate().take(ahi).skip(alo) {
+        let mut new_j2len = HashMap::new();
+        for &j in b2j.get(line).map(Vec::as_slice).unwrap_or_default() {
+            if j < blo {
+                continue;
+            }
+            if j >= bhi {
+                break;
+            }
+            let k = j
+                .checked_sub(1)
+                .and_then(|previous| j2len.get(&previous))
+                .copied()
+                .unwrap_or(0)
+                + 1;
+            new_j2len.insert(j, k);
+            if k > best_size {
+                best_i = i + 1 - k;
+                best_j = j + 1 - k;
+                best_size = k;
+            }
+        }
+        j2len = new_j2len;
+    }
+    // Popular lines are left out of `b2j`, so extend the match over equal ones around it.
+    while best_i > alo && best_j > blo && a[best_i - 1] == b[best_j - 1] {
+        best_i -= 1;
+        best_j -= 1;
+        best_size += 1;
+    }
+    while best_i + best_size < ahi
+        && best_j + best_size < bhi
+        && a[best_i + best_size] == b[best_j + best_size]
+    {
+        best_size += 1;
+    }
+    (best_i, best_j, best_size)
+}
@@ -0,0 +1,89 @@
+use fancy_regex::Regex;
+
+use crate::error::ContentError;
+
+/// A pattern from a content.json, such as `ignore` or `files_allowed`, matched like
+/// Python's `re.match`: anchored at the start only, with lookarounds and backreferences.
+#[derive(Debug, Clone)]
+pub struct Pattern {
+    pattern: String,
+    regex: Regex,
+}
+
+impl Pattern {
+    pub fn new(pattern: &str) -> Result<Pattern, ContentError> {
+        let regex = Regex::new(&format!("^(?:{})", pattern))
+            .map_err(|err| ContentError::Pattern(format!("{} in {}", err, pattern)))?;
+        Ok(Pattern {
+            pattern: pattern.to_owned(),
+            regex,
+        })
+    }
+
+    /// Like ZeroNet's SafeRe, refuses patterns that could backtrack catastrophically:
+    /// long ones, repetitions of anything but `.`, and ten or more repetitions.
+    pub fn safe(pattern: &str) -> Result<Pattern, ContentError> {
+        if pattern.chars().count() > 255 {
+            return Err(ContentError::Pattern(format!(
+                "pattern too long: {} characters in {}",
+                pattern.chars().count(),
+                pattern
+            )));
+        }
+        let chars: Vec<char> = pattern.chars().collect();
+        let is_repetition = |c: char| c == '*' || c == '{' || c == '+';
+        if let Some(unsafe_part) = chars
+            .windows(2)
+            .find(|pair| pair[0] != '.' && is_repetition(pair[1]))
+        {
+            return Err(ContentError::Pattern(format!(
+                "potentially unsafe part of the pattern: {}{} in {}",
+                unsafe_part[0], unsafe_part[1], pattern
+            )));
+        }
+        let mut repetitions = 0;
+        let mut i = 0;
+        while i + 1 < chars.len() {
+            if chars[i] == '.' && is_repetition(chars[i + 1]) {
+                repetitions += 1;
+                i += 2;
+            } else {
+                i += 1;
+            }
+        }
+        if repetitions >= 10 {
+            return Err(ContentError::Pattern(format!(
+                "more than 10 repetitions in {}",
+                pattern
+            )));
+        }
+        Pattern::new(pattern)
+    }
+
+    /// A safe pattern that must match the whole text, as ZeroNet checks `files_allowed`.
+    pub fn safe_full(pattern: &str) -> Result<Pattern, ContentError> {
+        Pattern::safe(&format!("^{}$", pattern))
+    }
+
+    /// A pattern that fails to run, such as one exceeding the backtracking limit, does not match.
+    pub fn is_match(&self, text: &str) -> bool {
+        self.regex.is_match(text).unwrap_or(false)
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.pattern
+    }
+}
+
+/// Compiles an optional pattern with the SafeRe checks, where an empty string means no
+/// pattern.
+pub(crate) fn compile(pattern: &str) -> Result<Option<Pattern>, ContentError> {
+    if pattern.is_empty() {
+        return Ok(None);
+    }
+    Pattern::safe(pattern).map(Some)
+}
+
+pub(crate) fn matches(pattern: &Option<Pattern>, text: &str) -> bool {
+    matches!(pattern, Some(pattern) if pattern.is_match(text))
+}
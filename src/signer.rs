@@ -0,0 +1,35 @@
+use crate::{error::ContentError, util::privkey_to_address};
+
+/// Source of signatures for a single address, such as a local key or a signing service.
+pub trait Signer {
+    fn address(&self) -> String;
+    /// Returns the base64 encoded compact signature of `data`.
+    fn sign(&self, data: &[u8]) -> Result<String, ContentError>;
+}
+
+/// Signs with a WIF encoded private key held in memory.
+#[derive(Clone)]
+pub struct PrivateKeySigner {
+    address: String,
+    privkey: String,
+}
+
+impl PrivateKeySigner {
+    pub fn new(privkey: &str) -> Result<PrivateKeySigner, ContentError> {
+        Ok(PrivateKeySigner {
+            address: privkey_to_address(privkey).map_err(ContentError::Sign)?,
+            privkey: privkey.to_owned(),
+        })
+    }
+}
+
+impl Signer for PrivateKeySigner {
+    fn address(&self) -> String {
+        self.address.clone()
+    }
+
+    fn sign(&self, data: &[u8]) -> Result<String, ContentError> {
+        zeronet_cryptography::sign(data, &self.privkey)
+            .map_err(|err| ContentError::Sign(err.to_string()))
+    }
+}
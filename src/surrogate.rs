@@ -0,0 +1,107 @@
+use std::borrow::Cow;
+use std::fmt::Write;
+
+use crate::error::ContentError;
+
+const FIRST_BLOCK: u32 = 0xF0000;
+const SURROGATES: u32 = 0x800;
+
+/// Lone UTF-16 surrogates, which Python's json reads from `\u` escapes but a Rust string
+/// cannot hold. They are parsed as characters of a private use block the document does not
+/// otherwise use, and written back as `\u` escapes. Object keys with a lone surrogate may
+/// sort differently than in Python.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct LoneSurrogates {
+    base: u32,
+}
+
+impl LoneSurrogates {
+    /// Rewrites the escapes of lone surrogates in `json` to those of the block's characters,
+    /// returning `None` when there are none.
+    pub(crate) fn encode(
+        json: &str,
+    ) -> Result<(Cow<'_, str>, Option<LoneSurrogates>), ContentError> {
+        let escapes = unicode_escapes(json.as_bytes());
+        let mut used: Vec<u32> = json
+            .chars()
+            .map(u32::from)
+            .filter(|c| *c >= FIRST_BLOCK)
+            .collect();
+        let mut lone = vec![];
+        let mut index = 0;
+        while index < escapes.len() {
+            let (start, unit) = escapes[index];
+            match unit {
+                0xD800..=0xDBFF => match escapes.get(index + 1) {
+                    Some(&(next, low @ 0xDC00..=0xDFFF)) if next == start + 6 => {
+                        used.push(0x10000 + ((unit - 0xD800) << 10) + (low - 0xDC00));
+                        index += 1;
+                    }
+                    _ => lone.push((start, unit)),
+                },
+                0xDC00..=0xDFFF => lone.push((start, unit)),
+                _ => {}
+            }
+            index += 1;
+        }
+        if lone.is_empty() {
+            return Ok((Cow::Borrowed(json), None));
+        }
+
+        let base = (0..0x20000 / SURROGATES)
+            .map(|block| FIRST_BLOCK + block * SURROGATES)
+            .find(|base| !used.iter().any(|c| (*base..base + SURROGATES).contains(c)))
+            .ok_or_else(|| {
+                ContentError::Schema("no private use block left for lone surrogates".to_owned())
+            })?;
+        let mut encoded = String::with_capacity(json.len() + lone.len() * 6);
+        let mut end = 0;
+        for (start, unit) in lone {
+            encoded.push_str(&json[end..start]);
+            let c = char::from_u32(base + unit - 0xD800).unwrap_or(char::REPLACEMENT_CHARACTER);
+            let mut units = [0; 2];
+            for unit in c.encode_utf16(&mut units) {
+                let _ = write!(encoded, "\\u{:04x}", unit);
+            }
+            end = start + 6;
+        }
+        encoded.push_str(&json[end..]);
+        Ok((Cow::Owned(encoded), Some(LoneSurrogates { base })))
+    }
+
+    /// The lone surrogate `c` stands for, if it is in the block.
+    pub(crate) fn decode(self, c: char) -> Option<u32> {
+        let offset = u32::from(c).checked_sub(self.base)?;
+        if offset < SURROGATES {
+            Some(0xD800 + offset)
+        } else {
+            None
+        }
+    }
+}
+
+/// Positions and code units of the `\uXXXX` escapes. Backslashes only occur in strings, so
+/// the text can be scanned without tracking them.
+fn unicode_escapes(json: &[u8]) -> Vec<(usize, u32)> {
+    let mut escapes = vec![];
+    let mut index = 0;
+    while index < json.len() {
+        if json[index] != b'\\' {
+            index += 1;
+            continue;
+        }
+        let unit = json
+            .get(index + 2..index + 6)
+            .filter(|_| json[index + 1] == b'u')
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u32::from_str_radix(hex, 16).ok());
+        match unit {
+            Some(unit) => {
+                escapes.push((index, unit));
+                index += 6;
+            }
+            None => index += 2,
+        }
+    }
+    escapes
+}
@@ -0,0 +1,201 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+use serde_bytes::ByteBuf;
+
+use crate::{
+    builder::walk, content::valid_signers, error::ContentError, user_contents::user_address,
+    Content,
+};
+
+/// A root content.json together with the nested content.json files of the site.
+#[derive(Default)]
+pub struct ContentTree {
+    contents: BTreeMap<String, Content>,
+}
+
+/// Rules that apply to a content.json, resolved along the chain of parents.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Rules {
+    /// The content.json the rules apply to.
+    pub content_inner_path: String,
+    /// Content.json files from the root down to `content_inner_path`.
+    pub chain: Vec<String>,
+    pub signers: Vec<String>,
+    pub signers_required: usize,
+    /// Pattern the files of the content.json must match, empty if any file is allowed.
+    pub files_allowed: String,
+    /// Smallest `max_size` along the chain.
+    pub max_size: Option<u64>,
+    pub includes_allowed: bool,
+    /// Set to the parent content.json when the rules come from its `user_contents`.
+    pub user_contents: Option<String>,
+}
+
+/// Inner path of the content.json in `directory`, which is empty for the site root.
+fn content_json_in(directory: &[&str]) -> String {
+    if directory.is_empty() {
+        "content.json".to_owned()
+    } else {
+        format!("{}/content.json", directory.join("/"))
+    }
+}
+
+impl ContentTree {
+    pub fn new(root: Content) -> ContentTree {
+        let mut tree = ContentTree::default();
+        tree.insert("content.json", root);
+        tree
+    }
+
+    /// Loads the root and every nested content.json found below `site_directory`.
+    pub fn load<P: AsRef<Path>>(site_directory: P) -> Result<ContentTree, ContentError> {
+        let mut tree = ContentTree::default();
+        for inner_path in walk(site_directory.as_ref())? {
+            if inner_path == "content.json" || inner_path.ends_with("/content.json") {
+                let buf = fs::read(site_directory.as_ref().join(&inner_path))?;
+                tree.insert(&inner_path, Content::from_buf(ByteBuf::from(buf))?);
+            }
+        }
+        if tree.root().is_none() {
+            return Err(ContentError::Schema(
+                "site has no root content.json".to_owned(),
+            ));
+        }
+        Ok(tree)
+    }
+
+    pub fn insert(&mut self, inner_path: &str, content: Content) {
+        self.contents.insert(inner_path.to_owned(), content);
+    }
+
+    pub fn get(&self, inner_path: &str) -> Option<&Content> {
+        self.contents.get(inner_path)
+    }
+
+    pub fn root(&self) -> Option<&Content> {
+        self.get("content.json")
+    }
+
+    /// Inner path of the content.json responsible for `inner_path`: the nearest one
+    /// listing the file, the one of the user directory it is in, or the nearest loaded
+    /// one otherwise.
+    pub fn governing(&self, inner_path: &str) -> Option<String> {
+        if self.contents.contains_key(inner_path) {
+            return Some(inner_path.to_owned());
+        }
+        let mut directory: Vec<&str> = inner_path.split('/').collect();
+        directory.pop();
+        let mut nearest = None;
+        loop {
+            let content_inner_path = content_json_in(&directory);
+            if let Some(content) = self.contents.get(&content_inner_path) {
+                let prefix_len = content_inner_path.len() - "content.json".len();
+                let relative_path = &inner_path[prefix_len..];
+                if content.get_file(relative_path).is_some() {
+                    return Some(content_inner_path);
+                }
+                // Like ZeroNet's getFileInfo, files below a user directory belong to the
+                // content.json of that user, whether it is loaded or not.
+                if content.user_contents.is_some() {
+                    return Some(match user_address(relative_path) {
+                        Some(user_address) => format!(
+                            "{}{}/content.json",
+                            &content_inner_path[..prefix_len],
+                            user_address
+                        ),
+                        None => content_inner_path,
+                    });
+                }
+                nearest.get_or_insert(content_inner_path);
+            }
+            if directory.pop().is_none() {
+                return nearest;
+            }
+        }
+    }
+
+    /// Rules for the content.json governing `inner_path`, `None` if it is not allowed.
+    pub fn rules(&self, inner_path: &str) -> Option<Rules> {
+        let content_inner_path =
+            if inner_path == "content.json" || inner_path.ends_with("/content.json") {
+                inner_path.to_owned()
+            } else {
+                self.governing(inner_path)?
+            };
+        self.content_rules(&content_inner_path)
+    }
+
+    fn content_rules(&self, content_inner_path: &str) -> Option<Rules> {
+        let root = self.root()?;
+        if content_inner_path == "content.json" {
+            return Some(Rules {
+                content_inner_path: content_inner_path.to_owned(),
+                chain: vec![content_inner_path.to_owned()],
+                signers: root.valid_signers(),
+                signers_required: root.signs_required.max(1),
+                includes_allowed: true,
+                ..Default::default()
+            });
+        }
+
+        // Like ZeroNet's getRules, look for the nearest parent with includes or user_contents.
+        let mut directory: Vec<&str> = content_inner_path.split('/').collect();
+        directory.pop();
+        directory.pop()?;
+        loop {
+            let parent_inner_path = content_json_in(&directory);
+            if let Some(parent) = self.contents.get(&parent_inner_path) {
+                let relative_path =
+                    &content_inner_path[parent_inner_path.len() - "content.json".len()..];
+                if !parent.includes.is_empty() {
+                    let include = parent.includes.get(relative_path)?;
+                    let parent_rules = self.content_rules(&parent_inner_path)?;
+                    if !parent_rules.includes_allowed {
+                        return None;
+                    }
+                    let mut chain = parent_rules.chain;
+                    chain.push(content_inner_path.to_owned());
+                    let max_size = match (parent_rules.max_size, include.max_size()) {
+                        (max_size, 0) => max_size,
+                        (Some(parent), max_size) => Some(parent.min(max_size)),
+                        (None, max_size) => Some(max_size),
+                    };
+                    return Some(Rules {
+                        content_inner_path: content_inner_path.to_owned(),
+                        chain,
+                        signers: valid_signers(content_inner_path, parent).ok()?,
+                        signers_required: (include.signers_required() as usize).max(1),
+                        files_allowed: include.files_allowed().to_owned(),
+                        max_size,
+                        includes_allowed: include.includes_allowed(),
+                        user_contents: None,
+                    });
+                }
+                if let Some(user_contents) = &parent.user_contents {
+                    let mut chain = self.content_rules(&parent_inner_path)?.chain;
+                    chain.push(content_inner_path.to_owned());
+                    let user_address = user_address(relative_path)?;
+                    let user_content = self.contents.get(content_inner_path);
+                    let rules = user_contents.user_rules(user_address, user_content).ok()?;
+                    let mut signers = rules.signers;
+                    if !signers.contains(&parent.address) {
+                        signers.push(parent.address.clone());
+                    }
+                    return Some(Rules {
+                        content_inner_path: content_inner_path.to_owned(),
+                        chain,
+                        signers,
+                        signers_required: 1,
+                        files_allowed: rules.files_allowed,
+                        max_size: rules.max_size.map(|max_size| max_size as u64),
+                        includes_allowed: false,
+                        user_contents: Some(parent_inner_path),
+                    });
+                }
+            }
+            directory.pop()?;
+        }
+    }
+}
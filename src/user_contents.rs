@@ -1,20 +1,43 @@
 use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
 use std::cmp::PartialEq;
 use std::collections::BTreeMap;
 use std::default::Default;
+use std::error::Error;
+use std::fmt::{self, Display};
+
+use crate::{
+    content::now,
+    diff::{changed_keys, MapDiff, UserContentsDiff},
+    error::ContentError,
+    pattern::Pattern,
+    util::{is_default, Number},
+    Content, File,
+};
 
 #[derive(Serialize, Deserialize, Default, PartialEq, Clone)]
 #[serde(default)]
 pub struct UserContents {
+    #[serde(skip_serializing_if = "is_default")]
     pub archived: BTreeMap<String, usize>,
+    #[serde(skip_serializing_if = "is_default")]
     pub archived_before: usize,
+    #[serde(skip_serializing_if = "is_default")]
     pub cert_signers: BTreeMap<String, Vec<String>>,
+    #[serde(skip_serializing_if = "is_default")]
     pub cert_signers_pattern: String,
+    // Always written, even empty, as ZeroNet indexes `permission_rules` and `permissions`.
     pub permission_rules: BTreeMap<String, PermissionRulesType>,
     pub permissions: BTreeMap<String, PermissionRulesType>,
+    #[serde(skip_serializing_if = "is_default")]
     pub content_inner_path: String,
+    #[serde(skip_serializing_if = "is_default")]
     pub optional: Option<String>,
+    #[serde(skip_serializing_if = "is_default")]
     pub relative_path: String,
+
+    #[serde(flatten)]
+    other: BTreeMap<String, Value>,
 }
 
 #[derive(Serialize, Deserialize, PartialEq, Clone)]
@@ -33,9 +56,314 @@ impl Default for PermissionRulesType {
 #[derive(Serialize, Deserialize, Default, PartialEq, Clone)]
 #[serde(default)]
 pub struct PermissionRules {
+    #[serde(skip_serializing_if = "is_default")]
     pub files_allowed: String,
+    #[serde(skip_serializing_if = "is_default")]
     pub files_allowed_optional: String,
-    pub max_size: usize,
-    pub max_size_optional: usize,
+    /// Limits are checked when set, even to `0`.
+    #[serde(skip_serializing_if = "is_default")]
+    pub max_size: Option<usize>,
+    #[serde(skip_serializing_if = "is_default")]
+    pub max_size_optional: Option<usize>,
+    #[serde(skip_serializing_if = "is_default")]
     pub signers: Vec<String>,
+
+    #[serde(flatten)]
+    other: BTreeMap<String, Value>,
+}
+
+#[derive(Debug)]
+pub enum UserContentError {
+    /// The user is banned by a `false` entry in `permissions`.
+    Banned(String),
+    /// The user directory was archived at or after the `modified` of the content.
+    Archived(String),
+    /// A file does not match `files_allowed` or `files_allowed_optional`.
+    FileNotAllowed(String),
+    TooLarge {
+        size: usize,
+        max_size: usize,
+    },
+    OptionalTooLarge {
+        size: usize,
+        max_size: usize,
+    },
+    Content(ContentError),
+}
+
+impl Display for UserContentError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            UserContentError::Banned(user) => write!(f, "{} is banned", user),
+            UserContentError::Archived(user_dir) => write!(f, "{} is archived", user_dir),
+            UserContentError::FileNotAllowed(inner_path) => {
+                write!(f, "{} is not allowed", inner_path)
+            }
+            UserContentError::TooLarge { size, max_size } => {
+                write!(f, "content too large: {} > {}", size, max_size)
+            }
+            UserContentError::OptionalTooLarge { size, max_size } => {
+                write!(f, "optional files too large: {} > {}", size, max_size)
+            }
+            UserContentError::Content(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl Error for UserContentError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            UserContentError::Content(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<ContentError> for UserContentError {
+    fn from(err: ContentError) -> Self {
+        UserContentError::Content(err)
+    }
+}
+
+impl From<serde_json::Error> for UserContentError {
+    fn from(err: serde_json::Error) -> Self {
+        UserContentError::Content(err.into())
+    }
+}
+
+impl UserContents {
+    /// Whether content of `user_dir` modified at `modified` was archived, like ZeroNet's
+    /// isArchived: it is not newer than `archived_before` or the entry in `archived`.
+    pub fn is_archived(&self, user_dir: &str, modified: &Number) -> bool {
+        let archived = self.archived.get(user_dir).copied().unwrap_or(0);
+        *modified <= Number::Integer(self.archived_before) || *modified <= Number::Integer(archived)
+    }
+
+    /// Archives `user_dir` as of now, so its current content is no longer accepted.
+    pub fn archive(&mut self, user_dir: &str) {
+        self.archived.insert(user_dir.to_owned(), now().into());
+    }
+
+    /// Archives every user directory not updated after `timestamp`, dropping the
+    /// `archived` entries it covers.
+    pub fn archive_before(&mut self, timestamp: usize) {
+        self.archived_before = self.archived_before.max(timestamp);
+        let archived_before = self.archived_before;
+        self.archived
+            .retain(|_, archived| *archived > archived_before);
+    }
+
+    /// Rejects a user content.json that is not newer than the archive of its directory.
+    pub fn check_archived(&self, user_content: &Content) -> Result<(), UserContentError> {
+        let user_dir = user_content.inner_path.rsplit('/').nth(1).unwrap_or("");
+        if self.is_archived(user_dir, &user_content.modified) {
+            return Err(UserContentError::Archived(user_dir.to_owned()));
+        }
+        Ok(())
+    }
+
+    /// What changed in the rules from `old`.
+    pub fn diff(&self, old: &UserContents) -> UserContentsDiff {
+        let mut fields = changed_keys(&old.other, &self.other);
+        let mut changed = |name: &str, changed: bool| {
+            if changed {
+                fields.push(name.to_owned());
+            }
+        };
+        changed("archived", self.archived != old.archived);
+        changed(
+            "archived_before",
+            self.archived_before != old.archived_before,
+        );
+        changed(
+            "cert_signers_pattern",
+            self.cert_signers_pattern != old.cert_signers_pattern,
+        );
+        changed(
+            "content_inner_path",
+            self.content_inner_path != old.content_inner_path,
+        );
+        changed("optional", self.optional != old.optional);
+        changed("relative_path", self.relative_path != old.relative_path);
+        fields.sort();
+        UserContentsDiff {
+            permissions: MapDiff::between(&old.permissions, &self.permissions),
+            permission_rules: MapDiff::between(&old.permission_rules, &self.permission_rules),
+            cert_signers: MapDiff::between(&old.cert_signers, &self.cert_signers),
+            fields,
+        }
+    }
+
+    /// Rules for a user content.json, merged like ZeroNet's getUserContentRules: the
+    /// `permissions` entry of the user, then every `permission_rules` pattern matching
+    /// `<cert_auth_type>/<cert_user_id>`. The user address is added to the signers.
+    pub fn rules_for(&self, user_content: &Content) -> Result<PermissionRules, UserContentError> {
+        let user_address = user_content.inner_path.rsplit('/').nth(1).unwrap_or("");
+        self.user_rules(user_address, Some(user_content))
+    }
+
+    /// Rules for the user at `user_address`, matched as `n-a/n-a` without its content.json.
+    pub fn user_rules(
+        &self,
+        user_address: &str,
+        user_content: Option<&Content>,
+    ) -> Result<PermissionRules, UserContentError> {
+        let (mut rules, banned) = self.merged_rules(user_address, user_content)?;
+        if banned {
+            return Err(UserContentError::Banned(user_address.to_owned()));
+        }
+        if !rules.signers.iter().any(|signer| signer == user_address) {
+            rules.signers.push(user_address.to_owned());
+        }
+        Ok(rules)
+    }
+
+    /// Addresses allowed to sign the content.json of `user_address`: the signers of its
+    /// rules and, unless banned, the user.
+    pub fn valid_signers(
+        &self,
+        user_address: &str,
+        user_content: Option<&Content>,
+    ) -> Result<Vec<String>, ContentError> {
+        let (rules, banned) = self.merged_rules(user_address, user_content)?;
+        let mut signers = rules.signers;
+        if !banned && !signers.iter().any(|signer| signer == user_address) {
+            signers.push(user_address.to_owned());
+        }
+        Ok(signers)
+    }
+
+    fn merged_rules(
+        &self,
+        user_address: &str,
+        user_content: Option<&Content>,
+    ) -> Result<(PermissionRules, bool), ContentError> {
+        let (cert_auth_type, cert_user_id) = match user_content {
+            Some(user_content) if !user_content.cert_user_id.is_empty() => (
+                user_content.cert_auth_type.as_str(),
+                user_content.cert_user_id.as_str(),
+            ),
+            _ => ("n-a", "n-a"),
+        };
+        let user_urn = format!("{}/{}", cert_auth_type, cert_user_id);
+        let permissions = self
+            .permissions
+            .get(user_address)
+            .or_else(|| self.permissions.get(cert_user_id));
+        let (mut rules, banned) = match permissions {
+            Some(PermissionRulesType::None(false)) => (Map::new(), true),
+            Some(PermissionRulesType::Rules(rules)) => (to_map(rules)?, false),
+            _ => (Map::new(), false),
+        };
+        for (pattern, permission_rules) in &self.permission_rules {
+            let permission_rules = match permission_rules {
+                PermissionRulesType::Rules(permission_rules) => permission_rules,
+                PermissionRulesType::None(_) => continue,
+            };
+            if !Pattern::safe(pattern)?.is_match(&user_urn) {
+                continue;
+            }
+            for (key, value) in to_map(permission_rules)? {
+                merge_rule(&mut rules, key, value);
+            }
+        }
+        let rules = PermissionRules::deserialize(Value::Object(rules))?;
+        Ok((rules, banned))
+    }
+
+    /// Checks a user content.json is not archived, then its files and sizes against its rules.
+    pub fn validate_user_content(
+        &self,
+        user_content: &Content,
+    ) -> Result<PermissionRules, UserContentError> {
+        self.check_archived(user_content)?;
+        let rules = self.rules_for(user_content)?;
+        check_files_allowed(&rules.files_allowed, &user_content.files)?;
+        check_files_allowed(&rules.files_allowed_optional, &user_content.files_optional)?;
+
+        let size = user_content.dumped_size()? + total_size(&user_content.files);
+        match rules.max_size {
+            Some(max_size) if size > max_size => {
+                return Err(UserContentError::TooLarge { size, max_size })
+            }
+            _ => {}
+        }
+        let size = total_size(&user_content.files_optional);
+        match rules.max_size_optional {
+            Some(max_size) if size > max_size => {
+                return Err(UserContentError::OptionalTooLarge { size, max_size })
+            }
+            _ => {}
+        }
+        Ok(rules)
+    }
+}
+
+/// The user directory `relative_path` is in, relative to the content.json with `user_contents`.
+pub(crate) fn user_address(relative_path: &str) -> Option<&str> {
+    let (user_address, _) = relative_path.split_once('/')?;
+    if !user_address.is_empty() && user_address.bytes().all(|b| b.is_ascii_alphanumeric()) {
+        Some(user_address)
+    } else {
+        None
+    }
+}
+
+fn to_map(rules: &PermissionRules) -> Result<Map<String, Value>, serde_json::Error> {
+    match serde_json::to_value(rules)? {
+        Value::Object(map) => Ok(map),
+        _ => Ok(Map::new()),
+    }
+}
+
+/// Keeps the larger number, `true`, the longer string and the union of lists.
+fn merge_rule(rules: &mut Map<String, Value>, key: String, value: Value) {
+    let current = match rules.get_mut(&key) {
+        Some(current) => current,
+        None => {
+            rules.insert(key, value);
+            return;
+        }
+    };
+    match (current, value) {
+        (Value::Number(current), Value::Number(value)) if value.as_f64() > current.as_f64() => {
+            *current = value
+        }
+        (current, Value::Bool(true)) => *current = Value::Bool(true),
+        (Value::String(current), Value::String(value))
+            if value.chars().count() > current.chars().count() =>
+        {
+            *current = value
+        }
+        (Value::Array(current), Value::Array(values)) => {
+            for value in values {
+                if !current.contains(&value) {
+                    current.push(value);
+                }
+            }
+        }
+        _ => {}
+    }
+}
+
+fn check_files_allowed(
+    pattern: &str,
+    files: &BTreeMap<String, File>,
+) -> Result<(), UserContentError> {
+    // Like ZeroNet, an empty pattern allows any file and a set one must match in full.
+    if pattern.is_empty() {
+        return Ok(());
+    }
+    let pattern = Pattern::safe_full(pattern)?;
+    match files
+        .keys()
+        .find(|inner_path| !pattern.is_match(inner_path))
+    {
+        Some(inner_path) => Err(UserContentError::FileNotAllowed(inner_path.clone())),
+        None => Ok(()),
+    }
+}
+
+fn total_size(files: &BTreeMap<String, File>) -> usize {
+    files.values().map(|file| file.size).sum()
 }
@@ -1,18 +1,104 @@
+use std::cmp::Ordering;
+use std::convert::TryFrom;
 use std::default::Default;
+use std::fmt::{self, Display};
+use std::io::{self, Read};
+use std::time::SystemTime;
 
-use serde::{Deserialize, Serialize};
+use bitcoin::{secp256k1::Secp256k1, Address, Network, PrivateKey};
+use serde::{de, Deserialize, Deserializer, Serialize};
+use sha2::{Digest, Sha512};
 
 pub fn is_default<T: Default + PartialEq>(t: &T) -> bool {
     t == &T::default()
 }
 
-#[derive(Serialize, Deserialize, Clone, Debug)]
+/// Hex digest in the format ZeroNet uses for hashes: the first 256 bits of sha512.
+pub fn sha512sum(data: &[u8]) -> String {
+    let digest = format!("{:x}", Sha512::digest(data));
+    digest[..64].to_owned()
+}
+
+/// Mirrors ZeroNet's `isValidRelativePath`, rejecting traversal, absolute
+/// and Windows reserved paths.
+pub fn is_valid_relative_path(path: &str) -> bool {
+    if path.is_empty()
+        || path.len() > 255
+        || path.starts_with(&['/', '\\'][..])
+        || path.ends_with(&['.', ' '][..])
+    {
+        return false;
+    }
+    let reserved = |segment: &str| {
+        let name = segment.split('.').next().unwrap_or("").to_ascii_uppercase();
+        match name.as_str() {
+            "CON" | "PRN" | "AUX" | "NUL" | "CONOUT$" | "CONIN$" => true,
+            _ if name.len() == 4 && (name.starts_with("COM") || name.starts_with("LPT")) => {
+                matches!(name.as_bytes()[3], b'1'..=b'9')
+            }
+            _ => false,
+        }
+    };
+    if path
+        .split(&['/', '\\'][..])
+        .any(|segment| segment == ".." || reserved(segment))
+    {
+        return false;
+    }
+    !path.chars().any(|c| c < '\x20' || "\"*:<>?\\|".contains(c))
+}
+
+/// Streaming variant of `sha512sum`, also returning the number of bytes read.
+pub fn sha512sum_reader<R: Read>(mut reader: R) -> io::Result<(String, usize)> {
+    let mut hasher = Sha512::new();
+    let mut buf = [0; 64 * 1024];
+    let mut size = 0;
+    loop {
+        let read = match reader.read(&mut buf) {
+            Ok(0) => break,
+            Ok(read) => read,
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+            Err(err) => return Err(err),
+        };
+        hasher.update(&buf[..read]);
+        size += read;
+    }
+    let digest = format!("{:x}", hasher.finalize());
+    Ok((digest[..64].to_owned(), size))
+}
+
+/// Address of the uncompressed or compressed key encoded in a WIF private key.
+pub fn privkey_to_address(privkey: &str) -> Result<String, String> {
+    let privkey = PrivateKey::from_wif(privkey).map_err(|err| err.to_string())?;
+    let pubkey = privkey.public_key(&Secp256k1::signing_only());
+    Ok(Address::p2pkh(&pubkey, Network::Bitcoin).to_string())
+}
+
+#[derive(Serialize, Clone, Copy, Debug)]
 #[serde(untagged)]
 pub enum Number {
     Integer(usize),
     Float(f64),
 }
 
+/// Read through `serde_json::Number`, which also takes float literals such as `1.50` that
+/// `arbitrary_precision` does not pass on as an f64.
+impl<'de> Deserialize<'de> for Number {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let number = serde_json::Number::deserialize(deserializer)?;
+        match (number.as_u64(), number.as_f64()) {
+            (Some(integer), _) if usize::try_from(integer).is_ok() => {
+                Ok(Number::Integer(integer as usize))
+            }
+            (_, Some(float)) => Ok(Number::Float(float)),
+            _ => Err(de::Error::custom(format!(
+                "number out of range: {}",
+                number
+            ))),
+        }
+    }
+}
+
 impl Default for Number {
     fn default() -> Self {
         Number::Integer(0)
@@ -33,3 +119,124 @@ impl From<u64> for Number {
         Number::Integer(i as usize)
     }
 }
+
+impl From<f64> for Number {
+    fn from(f: f64) -> Self {
+        Number::Float(f)
+    }
+}
+
+/// Whole seconds since the epoch, as ZeroNet writes `modified`.
+impl From<SystemTime> for Number {
+    fn from(time: SystemTime) -> Self {
+        let secs = time
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        Number::from(secs)
+    }
+}
+
+impl Number {
+    pub fn as_f64(&self) -> f64 {
+        match self {
+            Number::Integer(i) => *i as f64,
+            Number::Float(f) => *f,
+        }
+    }
+}
+
+/// Integers and floats compare exactly by value, so `1471656205.079839` sorts after
+/// `1471656205` and `2^53 + 1` after `2^53` as a float. NaN is equal to itself and greater
+/// than any number.
+impl Ord for Number {
+    fn cmp(&self, other: &Self) -> Ordering {
+        match (self, other) {
+            (Number::Integer(a), Number::Integer(b)) => a.cmp(b),
+            (Number::Integer(a), Number::Float(b)) => cmp_integer_float(*a, *b),
+            (Number::Float(a), Number::Integer(b)) => cmp_integer_float(*b, *a).reverse(),
+            (Number::Float(a), Number::Float(b)) => a
+                .partial_cmp(b)
+                .unwrap_or_else(|| a.is_nan().cmp(&b.is_nan())),
+        }
+    }
+}
+
+/// Compares without rounding the integer to a float: the integral part of the float is
+/// compared as an integer and its fractional part breaks ties.
+fn cmp_integer_float(integer: usize, float: f64) -> Ordering {
+    if float.is_nan() || float >= u128::MAX as f64 {
+        return Ordering::Less;
+    }
+    if float < 0.0 {
+        return Ordering::Greater;
+    }
+    let fract = if float.fract() > 0.0 {
+        Ordering::Less
+    } else {
+        Ordering::Equal
+    };
+    (integer as u128).cmp(&(float.trunc() as u128)).then(fract)
+}
+
+impl PartialOrd for Number {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for Number {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Number {}
+
+impl Display for Number {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Number::Integer(i) => write!(f, "{}", i),
+            Number::Float(float) => write!(f, "{}", python_float_repr(*float)),
+        }
+    }
+}
+
+/// Formats a float like Python's `repr`: the shortest digits that round-trip, in
+/// positional notation for exponents from -4 to 15 and scientific notation otherwise.
+pub(crate) fn python_float_repr(value: f64) -> String {
+    if value.is_nan() {
+        return "nan".to_owned();
+    }
+    if value.is_infinite() {
+        return if value > 0.0 { "inf" } else { "-inf" }.to_owned();
+    }
+    // Rust's `{:e}` also prints the shortest round-trip digits, such as `1.470340815228e9`.
+    let scientific = format!("{:e}", value.abs());
+    let (mantissa, exponent) = scientific.split_once('e').unwrap_or((&scientific, "0"));
+    let exponent: i32 = exponent.parse().unwrap_or(0);
+    let digits = mantissa.replace('.', "");
+    let sign = if value.is_sign_negative() { "-" } else { "" };
+    if (-4..16).contains(&exponent) {
+        let formatted = if exponent < 0 {
+            format!("0.{}{}", "0".repeat((-exponent - 1) as usize), digits)
+        } else {
+            let point = exponent as usize + 1;
+            if digits.len() > point {
+                format!("{}.{}", &digits[..point], &digits[point..])
+            } else {
+                format!("{}{}.0", digits, "0".repeat(point - digits.len()))
+            }
+        };
+        format!("{}{}", sign, formatted)
+    } else {
+        let exponent_sign = if exponent < 0 { '-' } else { '+' };
+        format!(
+            "{}{}e{}{:02}",
+            sign,
+            mantissa,
+            exponent_sign,
+            exponent.abs()
+        )
+    }
+}
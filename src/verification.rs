@@ -0,0 +1,202 @@
+use std::error::Error;
+use std::fmt::{self, Display};
+
+use crate::{error::ContentError, util::sha512sum};
+
+/// Outcome of a successful signature check.
+#[derive(Debug, Clone, PartialEq)]
+pub struct VerifyReport {
+    pub signer: String,
+    /// sha512sum of the canonical bytes the signature was checked against.
+    pub hash: String,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum VerifyFailure {
+    /// The signer has no entry in `signs`.
+    MissingSignature,
+    /// The signature is not a base64 encoded compact signature.
+    MalformedSignature,
+    /// The signer is not in the set of valid signers.
+    UnknownSigner,
+    /// The signature did not recover to the signer address.
+    AddressMismatch(String),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct VerifyError {
+    pub signer: String,
+    pub reason: VerifyFailure,
+    /// sha512sum of the canonical bytes, if they were produced.
+    pub hash: Option<String>,
+}
+
+/// Per-signer results of a threshold check over all entries in `signs`.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct ThresholdReport {
+    pub required: usize,
+    pub passed: Vec<VerifyReport>,
+    pub failed: Vec<VerifyError>,
+}
+
+impl ThresholdReport {
+    pub fn is_satisfied(&self) -> bool {
+        self.passed.len() >= self.required
+    }
+}
+
+#[derive(Debug)]
+pub enum ThresholdError {
+    NotEnoughSignatures(ThresholdReport),
+    InvalidSignersSign(VerifyError),
+    /// The content could not be turned back into canonical bytes.
+    Content(ContentError),
+}
+
+#[derive(Debug)]
+pub enum CertError {
+    /// The content has no `cert_user_id`.
+    MissingCert,
+    /// `cert_user_id` is not of the form `<name>@<domain>`.
+    InvalidUserId(String),
+    /// The domain has no cert signer and does not match `cert_signers_pattern`.
+    UnknownCertSigner(String),
+    /// `cert_sign` did not verify against any cert signer of the domain.
+    Signature(VerifyError),
+    Content(ContentError),
+}
+
+/// Reason an update of a content.json is refused.
+#[derive(Debug, Clone, PartialEq)]
+pub enum UpdateError {
+    /// `modified` is not newer than that of the accepted version.
+    NotNewer { modified: f64, accepted: f64 },
+    /// `modified` is too far ahead of the clock.
+    FarFuture { modified: f64, now: f64 },
+}
+
+impl From<ContentError> for CertError {
+    fn from(err: ContentError) -> Self {
+        CertError::Content(err)
+    }
+}
+
+impl Display for VerifyFailure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            VerifyFailure::MissingSignature => write!(f, "missing signature"),
+            VerifyFailure::MalformedSignature => write!(f, "malformed signature"),
+            VerifyFailure::UnknownSigner => write!(f, "unknown signer"),
+            VerifyFailure::AddressMismatch(err) => write!(f, "address mismatch: {}", err),
+        }
+    }
+}
+
+impl Display for VerifyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "verification for {} failed: {}",
+            self.signer, self.reason
+        )?;
+        if let Some(hash) = &self.hash {
+            write!(f, " (sha512: {})", hash)?;
+        }
+        Ok(())
+    }
+}
+
+impl Error for VerifyError {}
+
+impl Display for ThresholdError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ThresholdError::NotEnoughSignatures(report) => write!(
+                f,
+                "{} of {} required signatures are valid",
+                report.passed.len(),
+                report.required
+            ),
+            ThresholdError::InvalidSignersSign(err) => write!(f, "invalid signers_sign: {}", err),
+            ThresholdError::Content(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl Error for ThresholdError {}
+
+impl Display for UpdateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            UpdateError::NotNewer { modified, accepted } => write!(
+                f,
+                "modified {} is not newer than accepted {}",
+                modified, accepted
+            ),
+            UpdateError::FarFuture { modified, now } => {
+                write!(f, "modified {} is in the far future of {}", modified, now)
+            }
+        }
+    }
+}
+
+impl Error for UpdateError {}
+
+impl Display for CertError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CertError::MissingCert => write!(f, "missing cert_user_id"),
+            CertError::InvalidUserId(cert_user_id) => {
+                write!(f, "invalid cert_user_id: {}", cert_user_id)
+            }
+            CertError::UnknownCertSigner(domain) => write!(f, "invalid cert signer: {}", domain),
+            CertError::Signature(err) => write!(f, "invalid cert_sign: {}", err),
+            CertError::Content(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl Error for CertError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            CertError::Signature(err) => Some(err),
+            CertError::Content(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+/// Compact signatures are 65 bytes, which is 88 characters of padded base64.
+fn is_well_formed_signature(signature: &str) -> bool {
+    signature.len() == 88
+        && signature.ends_with('=')
+        && signature[..87]
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '+' || c == '/')
+}
+
+pub(crate) fn verify_signature(
+    data: &str,
+    signer: &str,
+    signature: &str,
+) -> Result<VerifyReport, VerifyError> {
+    let hash = sha512sum(data.as_bytes());
+    let reason = if !is_well_formed_signature(signature) {
+        VerifyFailure::MalformedSignature
+    } else {
+        match zeronet_cryptography::verify(data.as_bytes(), signer, signature) {
+            Ok(_) => {
+                return Ok(VerifyReport {
+                    signer: signer.to_owned(),
+                    hash,
+                })
+            }
+            Err(err) => VerifyFailure::AddressMismatch(err.to_string()),
+        }
+    };
+    Err(VerifyError {
+        signer: signer.to_owned(),
+        reason,
+        hash: Some(hash),
+    })
+}
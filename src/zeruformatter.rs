@@ -3,46 +3,170 @@ use serde_json::error::{Result};
 use serde_json::ser::{Formatter, Serializer};
 use std::io;
 
-#[derive(Clone, Debug)]
-pub struct ZeruFormatter;
+use crate::surrogate::LoneSurrogates;
+use crate::util::python_float_repr;
 
-pub fn to_writer_pretty<W, T: ?Sized>(writer: W, value: &T) -> Result<()>
+#[derive(Clone, Debug, Default)]
+pub struct ZeruFormatter {
+	/// Escape every non-ASCII code point as `\uXXXX` like Python's `ensure_ascii`, which
+	/// makes the output byte for byte that of `json.dumps(value, sort_keys=True)`.
+	pub ensure_ascii: bool,
+	/// Characters standing for the lone surrogates of the parsed content, escaped as those.
+	lone_surrogates: Option<LoneSurrogates>,
+	/// Spaces per level for Python's `json.dumps(value, indent=n)` layout, compact if `None`.
+	pub indent: Option<usize>,
+	level: usize,
+	has_value: bool,
+}
+
+impl ZeruFormatter {
+	pub fn python() -> ZeruFormatter {
+		ZeruFormatter {
+			ensure_ascii: true,
+			..Default::default()
+		}
+	}
+
+	pub fn python_indent(indent: usize) -> ZeruFormatter {
+		ZeruFormatter {
+			indent: Some(indent),
+			..ZeruFormatter::python()
+		}
+	}
+
+	pub(crate) fn with_lone_surrogates(self, lone_surrogates: Option<LoneSurrogates>) -> ZeruFormatter {
+		ZeruFormatter {
+			lone_surrogates,
+			..self
+		}
+	}
+
+	fn begin_value<W: ?Sized>(&mut self, writer: &mut W, first: bool) -> io::Result<()>
+	where
+		W: io::Write,
+	{
+		match self.indent {
+			Some(indent) => {
+				writer.write_all(if first { &b"\n"[..] } else { &b",\n"[..] })?;
+				writer.write_all(" ".repeat(indent * self.level).as_bytes())
+			}
+			None if first => Ok(()),
+			None => writer.write_all(b", "),
+		}
+	}
+
+	fn begin_nested<W: ?Sized>(&mut self, writer: &mut W, open: &[u8]) -> io::Result<()>
+	where
+		W: io::Write,
+	{
+		self.level += 1;
+		self.has_value = false;
+		writer.write_all(open)
+	}
+
+	fn end_nested<W: ?Sized>(&mut self, writer: &mut W, close: &[u8]) -> io::Result<()>
+	where
+		W: io::Write,
+	{
+		self.level -= 1;
+		if let (Some(indent), true) = (self.indent, self.has_value) {
+			writer.write_all(b"\n")?;
+			writer.write_all(" ".repeat(indent * self.level).as_bytes())?;
+		}
+		writer.write_all(close)
+	}
+}
+
+pub fn to_writer_with<W, T: ?Sized>(writer: W, value: &T, formatter: ZeruFormatter) -> Result<()>
 where
 	W: io::Write,
 	T: Serialize,
 {
-	let mut ser = Serializer::with_formatter(writer, ZeruFormatter);
+	let mut ser = Serializer::with_formatter(writer, formatter);
 	value.serialize(&mut ser)
 }
 
-pub fn to_vec_pretty<T: ?Sized>(value: &T) -> Result<Vec<u8>>
+/// Byte for byte what Python's `json.dumps(value, sort_keys=True)` writes for the same,
+/// already sorted, value with `ZeruFormatter::python()`.
+pub fn to_string_with<T: ?Sized>(value: &T, formatter: ZeruFormatter) -> Result<String>
 where
 	T: Serialize,
 {
 	let mut writer = Vec::with_capacity(128);
-	to_writer_pretty(&mut writer, value)?;
-	Ok(writer)
-}
-
-pub fn to_string_zero<T: ?Sized>(value: &T) -> Result<String>
-where
-	T: Serialize,
-{
-	let vec = to_vec_pretty(value)?;
+	to_writer_with(&mut writer, value, formatter)?;
 	let string = unsafe {
-		// We do not emit invalid UTF-8.
-		String::from_utf8_unchecked(vec)
+		// Strings are written from Rust strings or as ASCII escapes.
+		String::from_utf8_unchecked(writer)
 	};
 	Ok(string)
 }
 
 impl Formatter for ZeruFormatter {
+	/// Floats are written like Python's json.dumps, `1e+16` rather than `1e16`.
+	#[inline]
+	fn write_f64<W: ?Sized>(&mut self, writer: &mut W, value: f64) -> io::Result<()>
+	where
+		W: io::Write,
+	{
+		let repr = match python_float_repr(value).as_str() {
+			"nan" => "NaN".to_owned(),
+			"inf" => "Infinity".to_owned(),
+			"-inf" => "-Infinity".to_owned(),
+			repr => repr.to_owned(),
+		};
+		writer.write_all(repr.as_bytes())
+	}
+
+	/// Parsed numbers keep their text. Integers are exact in Python too, floats are re-read
+	/// and written like `write_f64`.
+	#[inline]
+	fn write_number_str<W: ?Sized>(&mut self, writer: &mut W, value: &str) -> io::Result<()>
+	where
+		W: io::Write,
+	{
+		if !value.contains(&['.', 'e', 'E'][..]) {
+			return writer.write_all(value.as_bytes());
+		}
+		match value.parse::<f64>() {
+			Ok(float) => self.write_f64(writer, float),
+			Err(_) => writer.write_all(value.as_bytes()),
+		}
+	}
+
+	/// Python escapes everything outside printable ASCII, astral characters as surrogate pairs.
+	#[inline]
+	fn write_string_fragment<W: ?Sized>(&mut self, writer: &mut W, fragment: &str) -> io::Result<()>
+	where
+		W: io::Write,
+	{
+		if !self.ensure_ascii {
+			return writer.write_all(fragment.as_bytes());
+		}
+		let mut start = 0;
+		for (index, c) in fragment.char_indices() {
+			if c.is_ascii() && c != '\x7f' {
+				continue;
+			}
+			writer.write_all(&fragment.as_bytes()[start..index])?;
+			if let Some(unit) = self.lone_surrogates.and_then(|s| s.decode(c)) {
+				write!(writer, "\\u{:04x}", unit)?;
+			} else {
+				let mut units = [0; 2];
+				for unit in c.encode_utf16(&mut units) {
+					write!(writer, "\\u{:04x}", unit)?;
+				}
+			}
+			start = index + c.len_utf8();
+		}
+		writer.write_all(&fragment.as_bytes()[start..])
+	}
+
 	#[inline]
 	fn begin_array<W: ?Sized>(&mut self, writer: &mut W) -> io::Result<()>
 	where
 		W: io::Write,
 	{
-		writer.write_all(b"[")
+		self.begin_nested(writer, b"[")
 	}
 
 	#[inline]
@@ -50,7 +174,7 @@ impl Formatter for ZeruFormatter {
 	where
 		W: io::Write,
 	{
-		writer.write_all(b"]")
+		self.end_nested(writer, b"]")
 	}
 
 	#[inline]
@@ -58,10 +182,7 @@ impl Formatter for ZeruFormatter {
 	where
 		W: io::Write,
 	{
-		if !first {
-			return writer.write_all(b", ");
-		}
-		Ok(())
+		self.begin_value(writer, first)
 	}
 
 	#[inline]
@@ -69,6 +190,7 @@ impl Formatter for ZeruFormatter {
 	where
 		W: io::Write,
 	{
+		self.has_value = true;
 		Ok(())
 	}
 
@@ -77,7 +199,7 @@ impl Formatter for ZeruFormatter {
 	where
 		W: io::Write,
 	{
-		writer.write_all(b"{")
+		self.begin_nested(writer, b"{")
 	}
 
 	#[inline]
@@ -85,7 +207,7 @@ impl Formatter for ZeruFormatter {
 	where
 		W: io::Write,
 	{
-		writer.write_all(b"}")
+		self.end_nested(writer, b"}")
 	}
 
 	#[inline]
@@ -93,11 +215,7 @@ impl Formatter for ZeruFormatter {
 	where
 		W: io::Write,
 	{
-		if first {
-			writer.write_all(b"")
-		} else {
-			writer.write_all(b", ")
-		}
+		self.begin_value(writer, first)
 	}
 
 	#[inline]
@@ -113,6 +231,7 @@ impl Formatter for ZeruFormatter {
 	where
 		W: io::Write,
 	{
+		self.has_value = true;
 		Ok(())
 	}
 }
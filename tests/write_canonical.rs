@@ -0,0 +1,114 @@
+// Peak memory of `write_canonical`, measured with a global allocator of its own so it
+// does not count the allocations of the library tests.
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+use std::time::Instant;
+
+use zerucontent::{content, Content, File};
+
+const SITE_KEY: (&str, &str) = (
+    "1M3BMyg4m5JLD82Be3Z4PSzLv2U8GdJGmc",
+    "5JkC2hgWX1zqi5FMDuRwu9STpRk2tvfuY22Ac3HDMY2BWn1ofJT",
+);
+
+/// Counts the bytes allocated by the current thread, so tests running in parallel
+/// can measure the peak memory of their own code.
+struct PeakAlloc;
+
+thread_local! {
+    static ALLOCATED: Cell<(usize, usize)> = const { Cell::new((0, 0)) };
+}
+
+unsafe impl GlobalAlloc for PeakAlloc {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let _ = ALLOCATED.try_with(|allocated| {
+            let (current, peak) = allocated.get();
+            let current = current + layout.size();
+            allocated.set((current, peak.max(current)));
+        });
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        let _ = ALLOCATED.try_with(|allocated| {
+            let (current, peak) = allocated.get();
+            allocated.set((current.saturating_sub(layout.size()), peak));
+        });
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static PEAK_ALLOC: PeakAlloc = PeakAlloc;
+
+/// Runs `f` and returns its result with the peak bytes it held on this thread.
+fn peak_memory<T>(f: impl FnOnce() -> T) -> (T, usize) {
+    let start = ALLOCATED.with(|allocated| {
+        let (current, _) = allocated.get();
+        allocated.set((current, current));
+        current
+    });
+    let result = f();
+    let (_, peak) = ALLOCATED.with(|allocated| allocated.get());
+    (result, peak - start)
+}
+
+fn content_with_files(count: usize) -> Content {
+    let mut content = Content::create(SITE_KEY.0.to_string(), 0);
+    for i in 0..count {
+        let file = File {
+            sha512: format!("{:064x}", i),
+            size: i,
+        };
+        content
+            .files
+            .insert(format!("data/{}.json", i), file.clone());
+        if i % 2 == 0 {
+            content
+                .files_optional
+                .insert(format!("optional/{}.mp4", i), file);
+        }
+    }
+    content
+}
+
+#[test]
+fn test_write_canonical_memory() {
+    let mut content = content_with_files(5000);
+    content.sign_in_place(SITE_KEY.1).unwrap();
+    let (dumped, dump_peak) = peak_memory(|| content::dump(&content).unwrap());
+    let (written, write_peak) = peak_memory(|| {
+        let mut buf = Vec::with_capacity(dumped.len());
+        content.write_canonical(&mut buf).unwrap();
+        buf
+    });
+    assert_eq!(written, dumped.as_bytes());
+    assert!(
+        write_peak < dump_peak / 2,
+        "{} >= {} / 2",
+        write_peak,
+        dump_peak
+    );
+}
+
+#[test]
+#[ignore]
+fn bench_write_canonical() {
+    let content = content_with_files(50000);
+    let start = Instant::now();
+    let (dumped, peak) = peak_memory(|| content::dump(&content).unwrap());
+    println!(
+        "dump: {:?}, peak {} KiB for {} KiB",
+        start.elapsed(),
+        peak / 1024,
+        dumped.len() / 1024
+    );
+    let start = Instant::now();
+    let (_, peak) = peak_memory(|| content.write_canonical(std::io::sink()).unwrap());
+    println!(
+        "write_canonical: {:?}, peak {} KiB",
+        start.elapsed(),
+        peak / 1024
+    );
+}